@@ -0,0 +1,202 @@
+// backup.rs
+
+use crate::privilege::FsOps;
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Where every backup directory lives, relative to the working directory
+pub const BACKUPS_DIR: &str = "backups";
+/// Journal of everything a restore changed, replayed by revert
+pub const JOURNAL_FILE: &str = "journal.toml";
+/// Subdirectory holding the replaced originals
+pub const FILES_DIR: &str = "files";
+/// Sidecar describing where a backup came from
+pub const META_FILE: &str = "backup.toml";
+
+/// Metadata written next to every backup, shown in the revert browser
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct BackupMeta {
+    #[serde(default)]
+    pub kind: BackupKind,
+    /// For revert snapshots, the backup that was reverted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    pub created: String,
+    pub source_archive: String,
+    pub manifest_sha256: String,
+    pub hostname: String,
+    pub items: Vec<String>,
+    /// Files kept in the backup and their combined size in bytes
+    pub file_count: u64,
+    pub total_size: u64,
+}
+
+pub fn load_meta(backup_dir: &Path) -> Option<BackupMeta> {
+    let content = fs::read_to_string(backup_dir.join(META_FILE)).ok()?;
+    toml::from_str(&content).ok()
+}
+
+/// Number of files and total bytes under `dir`
+pub fn tree_stats(dir: &Path) -> (u64, u64) {
+    walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_file())
+        .fold((0, 0), |(count, size), e| {
+            (count + 1, size + e.metadata().map(|m| m.len()).unwrap_or(0))
+        })
+}
+
+/// Human-readable byte size, e.g. `2.0 GiB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChangeKind {
+    /// Did not exist before; revert deletes it
+    Created,
+    /// Existed before; the original was moved into the backup
+    Replaced,
+    /// Directory created to hold restored files; revert removes it if empty
+    DirAdded,
+    /// Empty directory removed by a revert; reverting again recreates it
+    DirRemoved,
+}
+
+/// Which operation produced a backup
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackupKind {
+    #[default]
+    Restore,
+    Revert,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JournalEntry {
+    pub kind: ChangeKind,
+    /// Absolute destination that was changed
+    pub path: PathBuf,
+    /// Original copy, relative to the backup directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup: Option<PathBuf>,
+    /// Written through sudo/pkexec
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub system: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Journal {
+    #[serde(default)]
+    pub entries: Vec<JournalEntry>,
+}
+
+/// Read the journal of a backup; `None` only for older backups written before restores kept one
+pub fn load_journal(backup_dir: &Path) -> io::Result<Option<Journal>> {
+    let content = match fs::read_to_string(backup_dir.join(JOURNAL_FILE)) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    toml::from_str(&content)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("invalid {}: {}", JOURNAL_FILE, e)))
+}
+
+/// A backup directory being filled while a restore runs
+pub struct Backup {
+    pub dir: PathBuf,
+    pub journal: Journal,
+}
+
+impl Backup {
+    pub fn new(timestamp: &str) -> Self {
+        // Two operations in the same second must not share a backup
+        let mut dir = PathBuf::from(BACKUPS_DIR).join(timestamp);
+        let mut n = 1;
+        while dir.exists() {
+            n += 1;
+            dir = PathBuf::from(BACKUPS_DIR).join(format!("{timestamp}_{n}"));
+        }
+        Backup {
+            dir,
+            journal: Journal::default(),
+        }
+    }
+
+    /// Where the original of `rel` is kept inside this backup
+    pub fn file_path(&self, rel: &Path) -> PathBuf {
+        self.dir.join(FILES_DIR).join(rel)
+    }
+
+    /// Move whatever is at `dest` into the backup, recording it as replaced or created
+    pub fn stash(&mut self, dest: &Path, rel: &Path, ops: &FsOps) -> io::Result<()> {
+        if dest.symlink_metadata().is_ok() {
+            let backup_path = self.file_path(rel);
+            fs::create_dir_all(backup_path.parent().unwrap())?;
+            ops.rename(dest, &backup_path)?;
+            self.record(ChangeKind::Replaced, dest, Some(Path::new(FILES_DIR).join(rel)), ops)
+        } else {
+            self.record(ChangeKind::Created, dest, None, ops)
+        }
+    }
+
+    /// Create `dir` and its missing parents, recording each one that is new
+    pub fn create_dirs(&mut self, dir: &Path, ops: &FsOps) -> io::Result<()> {
+        let missing: Vec<PathBuf> = dir
+            .ancestors()
+            .take_while(|d| !d.as_os_str().is_empty() && !d.exists())
+            .map(Path::to_path_buf)
+            .collect();
+
+        ops.create_dir_all(dir)?;
+        for added in missing.iter().rev() {
+            self.record(ChangeKind::DirAdded, added, None, ops)?;
+        }
+        Ok(())
+    }
+
+    /// Write the `backup.toml` sidecar once the restore is done
+    pub fn finish(&self, mut meta: BackupMeta) -> io::Result<()> {
+        if !self.dir.exists() {
+            return Ok(());
+        }
+        let (file_count, total_size) = tree_stats(&self.dir.join(FILES_DIR));
+        meta.file_count = file_count;
+        meta.total_size = total_size;
+
+        let content = toml::to_string_pretty(&meta)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(self.dir.join(META_FILE), content)
+    }
+
+    /// Append to the journal and persist it right away, so a failed restore can still be reverted
+    pub fn record(&mut self, kind: ChangeKind, path: &Path, backup: Option<PathBuf>, ops: &FsOps) -> io::Result<()> {
+        self.journal.entries.push(JournalEntry {
+            kind,
+            path: path.to_path_buf(),
+            backup,
+            system: ops.is_escalated(),
+        });
+        fs::create_dir_all(&self.dir)?;
+        let content = toml::to_string_pretty(&self.journal)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(self.dir.join(JOURNAL_FILE), content)
+    }
+}
@@ -0,0 +1,383 @@
+// check.rs
+
+use crate::detectors;
+use crate::packager;
+use crate::paths;
+
+use glob::Pattern;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use toml_edit::{ImDocument, Item, TableLike};
+
+// 📐 Keys each table of a targets file may have
+const ROOT_KEYS: &[&str] = &["include", "config", "retention", "restore", "profile"];
+const ENTRY_KEYS: &[&str] = &["name", "path", "policy", "exclude", "tags", "when", "template"];
+const WHEN_KEYS: &[&str] = &["hostname", "distro", "command", "env", "desktop"];
+const RETENTION_KEYS: &[&str] = &["keep_last", "keep_daily", "keep_weekly", "max_total_size", "auto"];
+const RESTORE_KEYS: &[&str] = &["allowed_roots", "max_total_size", "max_entries"];
+const PROFILE_KEYS: &[&str] = &["tags", "names", "exclude_tags"];
+
+#[derive(PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Where in a file a problem is, for the `file:line:column` pointer and the underlined source line
+#[derive(Clone)]
+struct Location {
+    line: usize,
+    column: usize,
+    width: usize,
+    text: String,
+}
+
+impl Location {
+    fn new(source: &str, span: Range<usize>) -> Self {
+        let start = span.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..].find('\n').map_or(source.len(), |i| start + i);
+        let text = source[line_start..line_end].to_string();
+        let column = source[line_start..start].chars().count() + 1;
+        let width = source[start..span.end.clamp(start, line_end)].chars().count().max(1);
+        Location {
+            line: source[..start].matches('\n').count() + 1,
+            column,
+            width,
+            text,
+        }
+    }
+}
+
+pub struct Diagnostic {
+    pub severity: Severity,
+    file: PathBuf,
+    location: Option<Location>,
+    message: String,
+}
+
+/// Everything wrong with a targets file and the files it includes
+pub struct Report {
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Report {
+    pub fn errors(&self) -> usize {
+        self.diagnostics.iter().filter(|d| d.severity == Severity::Error).count()
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.diagnostics.iter().filter(|d| d.severity == Severity::Warning).count()
+    }
+
+    /// Print each problem rustc-style, pointing at the offending source
+    pub fn print(&self) {
+        for d in &self.diagnostics {
+            match d.severity {
+                Severity::Error => println!("❌ error: {}", d.message),
+                Severity::Warning => println!("⚠️  warning: {}", d.message),
+            }
+            match &d.location {
+                Some(loc) => {
+                    let gutter = " ".repeat(loc.line.to_string().len());
+                    println!("{} --> {}:{}:{}", gutter, d.file.display(), loc.line, loc.column);
+                    println!("{} |", gutter);
+                    println!("{} | {}", loc.line, loc.text);
+                    println!("{} | {}{}", gutter, " ".repeat(loc.column - 1), "^".repeat(loc.width));
+                }
+                None => println!(" --> {}", d.file.display()),
+            }
+            println!();
+        }
+    }
+}
+
+/// A `[[config]]` entry that survived layering, kept for the checks across entries
+struct Entry {
+    name: String,
+    /// As written in the file, for messages
+    raw_path: String,
+    path: String,
+    exclude: Vec<Pattern>,
+    file: PathBuf,
+    location: Option<Location>,
+}
+
+#[derive(Default)]
+struct Checker {
+    diagnostics: Vec<Diagnostic>,
+    entries: Vec<Entry>,
+}
+
+impl Checker {
+    fn report(&mut self, severity: Severity, file: &Path, location: Option<Location>, message: String) {
+        self.diagnostics.push(Diagnostic { severity, file: file.to_path_buf(), location, message });
+    }
+
+    /// Check one file, the files it includes first, like the loader layers them
+    fn file(&mut self, path: &Path, stack: &mut Vec<PathBuf>) {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => return self.report(Severity::Error, path, None, format!("could not read file: {}", e)),
+        };
+        let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if stack.contains(&canonical) {
+            return self.report(Severity::Error, path, None, "include cycle: this file includes itself".to_string());
+        }
+
+        let doc = match ImDocument::parse(source.as_str()) {
+            Ok(doc) => doc,
+            Err(e) => {
+                let location = e.span().map(|span| Location::new(&source, span));
+                return self.report(Severity::Error, path, location, e.message().trim().to_string());
+            }
+        };
+        let at = |span: Option<Range<usize>>| span.map(|span| Location::new(&source, span));
+
+        // 🧾 Types and required fields, exactly as the loader sees them
+        if let Some(e) = detectors::type_error(&source) {
+            self.report(Severity::Error, path, at(e.span()), e.message().to_string());
+        }
+
+        let root = doc.as_table();
+        self.unknown_keys(path, &source, root, ROOT_KEYS, "the targets file");
+
+        stack.push(canonical);
+        for (pattern, span) in strings(root.get("include")) {
+            match detectors::resolve_include(path, &pattern) {
+                Ok(files) => {
+                    for file in files {
+                        if file.exists() {
+                            self.file(&file, stack);
+                        } else {
+                            let message = format!("included file '{}' does not exist", file.display());
+                            self.report(Severity::Error, path, at(span.clone()), message);
+                        }
+                    }
+                }
+                Err(e) => self.report(Severity::Error, path, at(span), e.trim_start_matches("❌ ").to_string()),
+            }
+        }
+        stack.pop();
+
+        if let Some(table) = root.get("retention").and_then(Item::as_table_like) {
+            self.unknown_keys(path, &source, table, RETENTION_KEYS, "[retention]");
+        }
+        if let Some(table) = root.get("restore").and_then(Item::as_table_like) {
+            self.unknown_keys(path, &source, table, RESTORE_KEYS, "[restore]");
+        }
+        if let Some(profiles) = root.get("profile").and_then(Item::as_table_like) {
+            for (name, profile) in profiles.iter() {
+                if let Some(table) = profile.as_table_like() {
+                    self.unknown_keys(path, &source, table, PROFILE_KEYS, &format!("[profile.{}]", name));
+                }
+            }
+        }
+
+        let mut seen: Vec<(String, Option<Location>)> = vec![];
+        for table in tables(root.get("config")) {
+            self.entry(path, &source, table, &mut seen);
+        }
+    }
+
+    fn entry(&mut self, file: &Path, source: &str, table: &dyn TableLike, seen: &mut Vec<(String, Option<Location>)>) {
+        let at = |item: Option<&Item>| item.and_then(Item::span).map(|span| Location::new(source, span));
+        let name = table.get("name").and_then(Item::as_str).unwrap_or("?").to_string();
+        let context = format!("[[config]] '{}'", name);
+        self.unknown_keys(file, source, table, ENTRY_KEYS, &context);
+        if let Some(when) = table.get("when").and_then(Item::as_table_like) {
+            self.unknown_keys(file, source, when, WHEN_KEYS, &format!("the `when` of '{}'", name));
+            if let Some(pattern) = when.get("hostname").and_then(Item::as_str) {
+                if let Err(e) = Pattern::new(pattern) {
+                    let location = at(when.get("hostname"));
+                    self.report(Severity::Error, file, location, format!("invalid hostname pattern: {}", e));
+                }
+            }
+        }
+
+        let mut exclude = vec![];
+        for (pattern, span) in strings(table.get("exclude")) {
+            match Pattern::new(&pattern) {
+                Ok(p) => exclude.push(p),
+                Err(e) => {
+                    let location = span.map(|span| Location::new(source, span));
+                    self.report(Severity::Error, file, location, format!("invalid exclude pattern '{}': {}", pattern, e));
+                }
+            }
+        }
+
+        // 👯 Within one file a repeated name is a mistake; across includes it is an override
+        let name_location = at(table.get("name"));
+        if let Some((_, first)) = seen.iter().find(|(n, _)| *n == name) {
+            let first = first.as_ref().map(|l| format!(" (first defined on line {})", l.line)).unwrap_or_default();
+            self.report(Severity::Error, file, name_location.clone(), format!("duplicate name '{}'{}", name, first));
+        }
+        seen.push((name.clone(), name_location));
+
+        let Some(raw_path) = table.get("path").and_then(Item::as_str) else {
+            return;
+        };
+        let path_location = at(table.get("path"));
+        let path = match paths::expand_vars(raw_path) {
+            Ok(path) => path,
+            Err(e) => return self.report(Severity::Error, file, path_location, e),
+        };
+
+        let entry = Entry { name, raw_path: raw_path.to_string(), path, exclude, file: file.to_path_buf(), location: path_location };
+        match self.entries.iter_mut().find(|e| e.name == entry.name) {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+    }
+
+    fn unknown_keys(&mut self, file: &Path, source: &str, table: &dyn TableLike, known: &[&str], context: &str) {
+        for (key, _) in table.iter() {
+            if known.contains(&key) {
+                continue;
+            }
+            let location = table
+                .get_key_value(key)
+                .and_then(|(k, _)| k.span())
+                .map(|span| Location::new(source, span));
+            let hint = known
+                .iter()
+                .filter(|k| edit_distance(k, key) <= 2)
+                .min_by_key(|k| edit_distance(k, key))
+                .map(|k| format!(", did you mean `{}`?", k))
+                .unwrap_or_default();
+            self.report(Severity::Error, file, location, format!("unknown key `{}` in {}{}", key, context, hint));
+        }
+    }
+
+    /// Entries whose paths coincide or nest end up in the archive twice
+    fn overlaps(&mut self) {
+        let mut found = vec![];
+        for (i, outer) in self.entries.iter().enumerate() {
+            let outer_rel = paths::archive_rel(&outer.path);
+            for (j, inner) in self.entries.iter().enumerate() {
+                let Ok(below) = paths::archive_rel(&inner.path).strip_prefix(&outer_rel).map(Path::to_path_buf) else {
+                    continue;
+                };
+                let message = if below.as_os_str().is_empty() {
+                    if j <= i {
+                        continue;
+                    }
+                    format!("'{}' has the same path as '{}' ({})", inner.name, outer.name, outer.raw_path)
+                } else if packager::is_excluded(&below, &outer.exclude) {
+                    continue;
+                } else {
+                    format!(
+                        "'{}' ({}) is inside '{}' ({}), so it is archived twice; exclude it there or drop one",
+                        inner.name, inner.raw_path, outer.name, outer.raw_path
+                    )
+                };
+                found.push((inner.file.clone(), inner.location.clone(), message));
+            }
+        }
+        for (file, location, message) in found {
+            self.report(Severity::Warning, &file, location, message);
+        }
+    }
+}
+
+/// The tables of `config`, whether written as `[[config]]` or as an inline array
+fn tables(item: Option<&Item>) -> Vec<&dyn TableLike> {
+    match item {
+        Some(Item::ArrayOfTables(array)) => array.iter().map(|t| t as &dyn TableLike).collect(),
+        Some(Item::Value(value)) => value
+            .as_array()
+            .map(|array| array.iter().filter_map(|v| v.as_inline_table()).map(|t| t as &dyn TableLike).collect())
+            .unwrap_or_default(),
+        _ => vec![],
+    }
+}
+
+/// String values of an array, with their spans
+fn strings(item: Option<&Item>) -> Vec<(String, Option<Range<usize>>)> {
+    item.and_then(Item::as_array)
+        .map(|array| {
+            array
+                .iter()
+                .filter_map(|v| Some((v.as_str()?.to_string(), v.span())))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Levenshtein distance, for "did you mean" hints on misspelled keys
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = (previous + usize::from(ca != *cb)).min(row[j] + 1).min(current + 1);
+            previous = current;
+        }
+    }
+    row[b.len()]
+}
+
+/// Validate a targets file and everything it includes
+pub fn check(config_path: &Path) -> Report {
+    let mut checker = Checker::default();
+    checker.file(config_path, &mut vec![]);
+    checker.overlaps();
+    Report { diagnostics: checker.diagnostics }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check `content` as a targets file; each diagnostic as (line, column, message)
+    fn diagnostics(content: &str) -> Vec<(usize, usize, String)> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config_targets.toml");
+        fs::write(&path, content).unwrap();
+        check(&path)
+            .diagnostics
+            .into_iter()
+            .map(|d| {
+                let loc = d.location.expect("diagnostic without a location");
+                (loc.line, loc.column, d.message)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn duplicate_name_points_at_the_second_entry() {
+        let found = diagnostics(
+            "[[config]]\nname = \"Vim\"\npath = \"~/.vimrc\"\n\n[[config]]\nname = \"Vim\"\npath = \"~/.gvimrc\"\n",
+        );
+        assert_eq!(found, vec![(6, 8, "duplicate name 'Vim' (first defined on line 2)".to_string())]);
+    }
+
+    #[test]
+    fn unknown_key_suggests_the_closest_one() {
+        let found = diagnostics("[[config]]\nname = \"Vim\"\npath = \"~/.vimrc\"\n  tgas = [\"editor\"]\n");
+        assert_eq!(found, vec![(4, 3, "unknown key `tgas` in [[config]] 'Vim', did you mean `tags`?".to_string())]);
+    }
+
+    #[test]
+    fn nested_path_is_reported_on_the_inner_entry() {
+        let found = diagnostics(
+            "[[config]]\nname = \"Neovim\"\npath = \"~/.config/nvim\"\n\n[[config]]\nname = \"Init\"\npath = \"~/.config/nvim/init.lua\"\n",
+        );
+        let message = "'Init' (~/.config/nvim/init.lua) is inside 'Neovim' (~/.config/nvim), so it is archived twice; \
+                       exclude it there or drop one";
+        assert_eq!(found, vec![(7, 8, message.to_string())]);
+    }
+
+    #[test]
+    fn excluded_nested_path_is_not_an_overlap() {
+        let found = diagnostics(
+            "[[config]]\nname = \"Neovim\"\npath = \"~/.config/nvim\"\nexclude = [\"init.lua\"]\n\n\
+             [[config]]\nname = \"Init\"\npath = \"~/.config/nvim/init.lua\"\n",
+        );
+        assert!(found.is_empty(), "{:?}", found);
+    }
+}
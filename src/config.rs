@@ -1,16 +1,143 @@
 // config.rs
 
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// What to do when a restored item already exists on disk
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictPolicy {
+    /// Back up the live copy, then replace it
+    #[default]
+    Overwrite,
+    /// Leave the live copy untouched
+    SkipIfExists,
+    /// Write the archived copy next to the live one as `.restitch-new`
+    KeepBoth,
+    /// Replace only when the archived copy was modified more recently
+    NewerWins,
+    /// Three-way merge text files against the previous export
+    Merge,
+}
+
+impl ConflictPolicy {
+    pub const ALL: [ConflictPolicy; 5] = [
+        ConflictPolicy::Overwrite,
+        ConflictPolicy::SkipIfExists,
+        ConflictPolicy::KeepBoth,
+        ConflictPolicy::NewerWins,
+        ConflictPolicy::Merge,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConflictPolicy::Overwrite => "overwrite",
+            ConflictPolicy::SkipIfExists => "skip-if-exists",
+            ConflictPolicy::KeepBoth => "keep-both",
+            ConflictPolicy::NewerWins => "newer-wins",
+            ConflictPolicy::Merge => "merge",
+        }
+    }
+
+    /// The policy after this one, used when cycling in the TUI
+    pub fn next(&self) -> ConflictPolicy {
+        let idx = Self::ALL.iter().position(|p| p == self).unwrap_or(0);
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+}
+
+impl fmt::Display for ConflictPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|p| p.as_str() == s)
+            .ok_or_else(|| {
+                let names: Vec<&str> = Self::ALL.iter().map(|p| p.as_str()).collect();
+                format!("unknown policy '{}' (expected one of: {})", s, names.join(", "))
+            })
+    }
+}
+
+/// Owner and permissions recorded for system-level targets
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct Ownership {
+    pub uid: u32,
+    pub gid: u32,
+    pub mode: u32,
+}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ConfigItem {
     pub name: String,
     pub path: String,
     pub selected: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<ConflictPolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<Ownership>,
+    /// Glob patterns for files below a directory target that are left out of the archive
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    /// Free-form labels such as `shell`, `desktop`, `dev` or `secrets`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Text files hold `{{ name }}` placeholders, rendered with host variables on restore
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub template: bool,
+}
+
+/// A named `[profile.<name>]` selection from the targets file
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct Profile {
+    /// Entries carrying any of these tags
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Entries picked by name
+    #[serde(default)]
+    pub names: Vec<String>,
+    /// Entries carrying any of these tags are left out, even if picked above
+    #[serde(default)]
+    pub exclude_tags: Vec<String>,
+}
+
+impl Profile {
+    fn matches(&self, item: &ConfigItem) -> bool {
+        let picked = (self.tags.is_empty() && self.names.is_empty())
+            || item.tags.iter().any(|t| self.tags.contains(t))
+            || self.names.contains(&item.name);
+        picked && !item.tags.iter().any(|t| self.exclude_tags.contains(t))
+    }
+}
+
+/// Which items `--profile` and `--tag` pick; with neither, everything is picked
+#[derive(Debug, Default, Clone)]
+pub struct Selection {
+    pub profile: Option<Profile>,
+    pub tags: Vec<String>,
+}
+
+impl Selection {
+    pub fn is_empty(&self) -> bool {
+        self.profile.is_none() && self.tags.is_empty()
+    }
+
+    pub fn matches(&self, item: &ConfigItem) -> bool {
+        self.is_empty()
+            || self.profile.as_ref().is_some_and(|p| p.matches(item))
+            || item.tags.iter().any(|t| self.tags.contains(t))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ConfigManifest {
     pub items: Vec<ConfigItem>,
 }
-
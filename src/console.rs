@@ -0,0 +1,106 @@
+// console.rs
+
+use crate::merge::{self, FileMerge};
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+/// What a job started from the dashboard needs the screen for
+pub enum Message {
+    /// A line that would otherwise be printed
+    Line(String),
+    /// A yes/no question; the answer goes back on the sender
+    Confirm(String, Sender<bool>),
+    /// A question answered with a line of text
+    Ask(String, Sender<String>),
+    /// Merge conflicts to settle on the conflict screen, sent back once settled
+    Resolve(Vec<FileMerge>, Sender<Vec<FileMerge>>),
+    /// The job is over; `Err` holds why it failed
+    Finished(Result<(), String>),
+}
+
+thread_local! {
+    /// Set on a dashboard job's thread; everywhere else output goes to the terminal
+    static DASHBOARD: RefCell<Option<Sender<Message>>> = const { RefCell::new(None) };
+}
+
+fn dashboard() -> Option<Sender<Message>> {
+    DASHBOARD.with(|d| d.borrow().clone())
+}
+
+/// Run `work` on its own thread, with its output and questions sent to the dashboard
+pub fn spawn(work: impl FnOnce() -> Result<(), String> + Send + 'static) -> Receiver<Message> {
+    let (sender, messages) = mpsc::channel();
+    thread::spawn(move || {
+        DASHBOARD.with(|d| *d.borrow_mut() = Some(sender.clone()));
+        let result = work();
+        let _ = sender.send(Message::Finished(result));
+    });
+    messages
+}
+
+/// Print a line, or hand it to the dashboard when running as one of its jobs
+pub fn write_line(text: String) {
+    match dashboard() {
+        Some(sender) => {
+            let _ = sender.send(Message::Line(text));
+        }
+        None => println!("{}", text),
+    }
+}
+
+/// `println!` for everything the dashboard also runs
+macro_rules! say {
+    () => {
+        $crate::console::write_line(String::new())
+    };
+    ($($arg:tt)*) => {
+        $crate::console::write_line(format!($($arg)*))
+    };
+}
+pub(crate) use say;
+
+fn read_line() -> String {
+    let _ = io::stdout().flush();
+    let mut input = String::new();
+    let _ = io::stdin().read_line(&mut input);
+    input.trim_end_matches(['\n', '\r']).to_string()
+}
+
+/// Ask a yes/no question; anything but `y` is a no
+pub fn confirm(question: &str) -> bool {
+    if let Some(sender) = dashboard() {
+        let (reply, answer) = mpsc::channel();
+        return sender.send(Message::Confirm(question.to_string(), reply)).is_ok() && answer.recv().unwrap_or(false);
+    }
+    print!("{} [y/N]: ", question);
+    read_line().trim().eq_ignore_ascii_case("y")
+}
+
+/// Ask for a line of text
+pub fn ask(question: &str) -> String {
+    if let Some(sender) = dashboard() {
+        let (reply, answer) = mpsc::channel();
+        if sender.send(Message::Ask(question.to_string(), reply)).is_err() {
+            return String::new();
+        }
+        return answer.recv().unwrap_or_default();
+    }
+    print!("{}", question);
+    read_line()
+}
+
+/// Settle merge conflicts on the conflict screen
+pub fn resolve(merges: &mut Vec<FileMerge>) -> io::Result<()> {
+    let Some(sender) = dashboard() else {
+        return merge::run_conflict_ui(merges);
+    };
+    let (reply, settled) = mpsc::channel();
+    sender
+        .send(Message::Resolve(std::mem::take(merges), reply))
+        .map_err(|_| io::Error::other("the dashboard is gone"))?;
+    *merges = settled.recv().map_err(|_| io::Error::other("the dashboard is gone"))?;
+    Ok(())
+}
@@ -0,0 +1,626 @@
+// dashboard.rs
+
+use crate::backup;
+use crate::config::Selection;
+use crate::console::{self, say, Message};
+use crate::history::{self, Entry};
+use crate::merge::{self, FileMerge};
+use crate::packager::{self, OUTPUT_DIR};
+use crate::privilege::Escalation;
+use crate::restore::{self, ItemPicker, RestoreOptions};
+use crate::revert::{self, BackupList, RevertPicker};
+use crate::signing;
+use crate::tui::ExportScreen;
+use crate::ui::{self, Line, SelectList, TerminalGuard};
+
+use chrono::{DateTime, Local};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, Sender};
+
+use crossterm::event::{Event, KeyCode, KeyEvent};
+use tui::{
+    backend::Backend,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    text::Spans,
+    widgets::{Block, Borders, Clear, Paragraph, Tabs, Wrap},
+    Frame, Terminal,
+};
+
+const TABS: [&str; 4] = ["📦 Export", "🔄 Restore", "🔁 Revert", "📜 History"];
+
+fn key_of(event: Event) -> Option<KeyEvent> {
+    match event {
+        Event::Key(key) => Some(key),
+        _ => None,
+    }
+}
+
+/// The manifest written next to an exported archive
+fn manifest_for(archive: &Path) -> PathBuf {
+    let name = archive.file_name().unwrap_or_default().to_string_lossy();
+    let stem = name.strip_suffix(".tar.gz").unwrap_or(&name);
+    archive.with_file_name(format!("{stem}.manifest.toml"))
+}
+
+/// Exports in the output directory, newest first; merge bases are left out
+fn list_archives() -> Vec<PathBuf> {
+    let mut archives: Vec<(Option<std::time::SystemTime>, PathBuf)> = fs::read_dir(OUTPUT_DIR)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|e| e.path())
+                .filter(|path| {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    name.ends_with(".tar.gz") && !name.ends_with(".prev.tar.gz")
+                })
+                .map(|path| (fs::metadata(&path).and_then(|m| m.modified()).ok(), path))
+                .collect()
+        })
+        .unwrap_or_default();
+    archives.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    archives.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Lines for the details pane of an archive: when it was made, whether it is signed, what it holds
+fn archive_details(archive: &Path) -> Vec<String> {
+    let mut lines = vec![];
+    if let Ok(meta) = fs::metadata(archive) {
+        lines.push(format!("Size:      {}", backup::format_size(meta.len())));
+        if let Ok(modified) = meta.modified() {
+            lines.push(format!("Exported:  {}", DateTime::<Local>::from(modified).format("%Y-%m-%d %H:%M")));
+        }
+    }
+    let signed = if signing::signature_path(archive).exists() { "yes, checked on restore" } else { "no" };
+    lines.push(format!("Signed:    {}", signed));
+
+    let manifest_path = manifest_for(archive);
+    lines.push(format!("Manifest:  {}", manifest_path.display()));
+    match restore::read_manifest(&manifest_path) {
+        Ok((manifest, _)) => {
+            lines.push(format!("Items:     {}", manifest.items.len()));
+            lines.push(String::new());
+            for item in &manifest.items {
+                let template = if item.template { "  🧩" } else { "" };
+                lines.push(format!("{}  {}{}", item.name, item.path, template));
+            }
+        }
+        Err(e) => {
+            lines.push(String::new());
+            lines.push(e);
+        }
+    }
+    lines
+}
+
+/// Archives in `outputs/`; opening one lists its items for restoring
+#[derive(Default)]
+struct RestoreTab {
+    archives: Vec<PathBuf>,
+    list: SelectList,
+    details: HashMap<usize, Vec<String>>,
+    /// The archive whose items are being picked, with its manifest digest
+    picking: Option<(PathBuf, String, ItemPicker)>,
+    status: String,
+}
+
+impl RestoreTab {
+    fn load() -> Self {
+        RestoreTab { archives: list_archives(), ..Default::default() }
+    }
+
+    fn open(&mut self, options: &RestoreOptions) {
+        let Some(archive) = self.list.selected().map(|i| self.archives[i].clone()) else {
+            return;
+        };
+        match restore::read_manifest(&manifest_for(&archive)) {
+            Ok((manifest, digest)) => {
+                let picker = ItemPicker::new(&archive.to_string_lossy(), manifest.items, options);
+                self.picking = Some((archive, digest, picker));
+                self.status.clear();
+            }
+            Err(e) => self.status = e,
+        }
+    }
+
+    fn draw<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
+        if let Some((_, _, picker)) = &mut self.picking {
+            return picker.draw(f, area);
+        }
+
+        let names: Vec<Line> = self
+            .archives
+            .iter()
+            .map(|path| Line::new("", path.file_name().unwrap_or_default().to_string_lossy()))
+            .collect();
+        self.list.set_lines(names);
+        if let Some(i) = self.list.selected() {
+            self.details.entry(i).or_insert_with(|| archive_details(&self.archives[i]));
+        }
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(3)].as_ref())
+            .split(area);
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
+            .split(chunks[0]);
+
+        self.list.render(f, panes[0], "🌀 Restitch: Select Archive to Restore");
+        let text = match self.list.selected().and_then(|i| self.details.get(&i)) {
+            Some(lines) => lines.join("\n"),
+            None => format!("No exports in {}/ yet.\n\nPick targets on the Export tab and press p.", OUTPUT_DIR),
+        };
+        let panel = Paragraph::new(text)
+            .block(Block::default().title("Details").borders(Borders::ALL))
+            .wrap(Wrap { trim: false });
+        f.render_widget(panel, panes[1]);
+
+        let status = [format!("{} archive(s) in {}/", self.archives.len(), OUTPUT_DIR), self.status.clone()]
+            .join("   ")
+            .trim()
+            .to_string();
+        ui::help_bar(f, chunks[1], "↑↓: Navigate  /: Search  ↵: Pick Items  q: Quit", &status);
+    }
+}
+
+/// Backups in `backups/`; one is reverted whole, or picked from file by file
+#[derive(Default)]
+struct RevertTab {
+    backups: BackupList,
+    /// The backup whose steps are being picked
+    picking: Option<(PathBuf, RevertPicker)>,
+}
+
+impl RevertTab {
+    fn load() -> Self {
+        // No backups directory just means nothing was restored yet
+        RevertTab { backups: BackupList::load().unwrap_or_default(), ..Default::default() }
+    }
+
+    fn draw<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
+        match &mut self.picking {
+            Some((_, picker)) => picker.draw(f, area),
+            None => self.backups.draw(f, area),
+        }
+    }
+}
+
+/// The operation log, newest first
+#[derive(Default)]
+struct HistoryTab {
+    entries: Vec<Entry>,
+    error: Option<String>,
+    list: SelectList,
+}
+
+impl HistoryTab {
+    fn load() -> Self {
+        match history::load() {
+            Ok(entries) => HistoryTab { entries, ..Default::default() },
+            Err(e) => HistoryTab { error: Some(e), ..Default::default() },
+        }
+    }
+
+    fn draw<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
+        let lines = self
+            .entries
+            .iter()
+            .map(|e| {
+                let mark = if e.ok { "✅ " } else { "❌ " };
+                Line::new(mark, format!("{}  {:<7}  {}", e.time, e.operation, e.subject))
+            })
+            .collect();
+        self.list.set_lines(lines);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(3)].as_ref())
+            .split(area);
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(55), Constraint::Percentage(45)].as_ref())
+            .split(chunks[0]);
+
+        self.list.render(f, panes[0], "🌀 Restitch: Operation History");
+        let text = match self.list.selected().map(|i| &self.entries[i]) {
+            Some(e) => format!(
+                "Time:       {}\nOperation:  {}\nOn:         {}\nOutcome:    {}\n\n{}",
+                e.time,
+                e.operation,
+                e.subject,
+                if e.ok { "done" } else { "failed" },
+                e.detail
+            ),
+            None => "Nothing exported, restored or reverted yet.".to_string(),
+        };
+        let panel = Paragraph::new(text)
+            .block(Block::default().title("Details").borders(Borders::ALL))
+            .wrap(Wrap { trim: false });
+        f.render_widget(panel, panes[1]);
+
+        let status = match &self.error {
+            Some(e) => e.clone(),
+            None => format!("{} operation(s) in {}", self.entries.len(), history::history_path().display()),
+        };
+        ui::help_bar(f, chunks[1], "↑↓: Navigate  /: Search  q: Quit", &status);
+    }
+}
+
+/// A question from a running job
+enum Prompt {
+    Confirm(String, Sender<bool>),
+    Ask { question: String, input: String, reply: Sender<String> },
+}
+
+/// An export, restore or revert running on its own thread, with its output in a popup
+struct Job {
+    title: String,
+    lines: Vec<String>,
+    messages: Receiver<Message>,
+    prompt: Option<Prompt>,
+    outcome: Option<Result<(), String>>,
+    /// Lines scrolled up from the newest
+    scroll: usize,
+}
+
+impl Job {
+    fn start(title: &str, work: impl FnOnce() -> Result<(), String> + Send + 'static) -> Self {
+        Job {
+            title: title.to_string(),
+            lines: vec![],
+            messages: console::spawn(work),
+            prompt: None,
+            outcome: None,
+            scroll: 0,
+        }
+    }
+
+    fn push(&mut self, text: &str) {
+        self.lines.extend(text.split('\n').map(str::to_string));
+        self.scroll = 0;
+    }
+
+    /// Take in what the job sent since the last frame; conflicts to settle are handed back
+    fn poll(&mut self) -> Option<(Vec<FileMerge>, Sender<Vec<FileMerge>>)> {
+        while let Ok(message) = self.messages.try_recv() {
+            match message {
+                Message::Line(text) => self.push(&text),
+                Message::Confirm(question, reply) => self.prompt = Some(Prompt::Confirm(question, reply)),
+                Message::Ask(question, reply) => {
+                    self.prompt = Some(Prompt::Ask { question, input: String::new(), reply });
+                }
+                Message::Resolve(merges, reply) => return Some((merges, reply)),
+                Message::Finished(result) => {
+                    if let Err(e) = &result {
+                        self.push(e);
+                    }
+                    self.outcome = Some(result);
+                }
+            }
+        }
+        None
+    }
+
+    /// Answer questions and scroll the output; `true` once a finished job is closed
+    fn handle(&mut self, event: Event) -> bool {
+        let Some(key) = key_of(event) else {
+            return false;
+        };
+        match self.prompt.take() {
+            Some(Prompt::Confirm(question, reply)) => {
+                let answer = match key.code {
+                    KeyCode::Char('y') | KeyCode::Char('Y') => true,
+                    KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => false,
+                    _ => {
+                        self.prompt = Some(Prompt::Confirm(question, reply));
+                        return false;
+                    }
+                };
+                self.push(&format!("{} [y/N]: {}", question, if answer { "y" } else { "n" }));
+                let _ = reply.send(answer);
+            }
+            Some(Prompt::Ask { question, mut input, reply }) => match key.code {
+                KeyCode::Enter | KeyCode::Esc => {
+                    if key.code == KeyCode::Esc {
+                        input.clear();
+                    }
+                    self.push(&format!("{}{}", question, input));
+                    let _ = reply.send(input);
+                }
+                code => {
+                    match code {
+                        KeyCode::Backspace => {
+                            input.pop();
+                        }
+                        KeyCode::Char(c) => input.push(c),
+                        _ => {}
+                    }
+                    self.prompt = Some(Prompt::Ask { question, input, reply });
+                }
+            },
+            None => match key.code {
+                KeyCode::Up | KeyCode::Char('k') => self.scroll += 1,
+                KeyCode::Down | KeyCode::Char('j') => self.scroll = self.scroll.saturating_sub(1),
+                KeyCode::PageUp => self.scroll += 10,
+                KeyCode::PageDown => self.scroll = self.scroll.saturating_sub(10),
+                KeyCode::Enter | KeyCode::Esc | KeyCode::Char('q') => return self.outcome.is_some(),
+                _ => {}
+            },
+        }
+        false
+    }
+
+    fn draw<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
+        let area = ui::centered(area, 85, 80);
+        f.render_widget(Clear, area);
+
+        let title = match &self.outcome {
+            None => format!("⏳ {}…", self.title),
+            Some(Ok(())) => format!("✅ {} finished  ↵: Close  ↑↓: Scroll", self.title),
+            Some(Err(_)) => format!("❌ {} failed  ↵: Close  ↑↓: Scroll", self.title),
+        };
+        let parts = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(3), Constraint::Length(if self.prompt.is_some() { 4 } else { 0 })].as_ref())
+            .split(area);
+
+        // Long lines are wrapped here, so the newest ones can stay in view unless scrolled back
+        let width = parts[0].width.saturating_sub(2).max(1) as usize;
+        let wrapped: Vec<String> = self
+            .lines
+            .iter()
+            .flat_map(|line| {
+                let chars: Vec<char> = line.chars().collect();
+                let rows: Vec<String> = chars.chunks(width).map(|row| row.iter().collect()).collect();
+                if rows.is_empty() { vec![String::new()] } else { rows }
+            })
+            .collect();
+        let height = parts[0].height.saturating_sub(2) as usize;
+        self.scroll = self.scroll.min(wrapped.len().saturating_sub(height));
+        let end = wrapped.len() - self.scroll;
+        let shown: Vec<Spans> = wrapped[end.saturating_sub(height)..end]
+            .iter()
+            .map(|line| Spans::from(line.as_str()))
+            .collect();
+        let output = Paragraph::new(shown).block(Block::default().title(title).borders(Borders::ALL));
+        f.render_widget(output, parts[0]);
+
+        let (title, text) = match &self.prompt {
+            None => return,
+            Some(Prompt::Confirm(question, _)) => ("❓ y: Yes  n: No", question.clone()),
+            Some(Prompt::Ask { question, input, .. }) => ("✏️  ↵: Answer  Esc: Leave empty", format!("{}{}▏", question, input)),
+        };
+        let prompt = Paragraph::new(text)
+            .block(Block::default().title(title).borders(Borders::ALL))
+            .wrap(Wrap { trim: false });
+        f.render_widget(prompt, parts[1]);
+    }
+}
+
+/// Every mode of restitch as one screen, a tab each
+struct Dashboard {
+    tab: usize,
+    config_path: String,
+    /// The export tab, or why the targets file could not be loaded
+    export: Result<ExportScreen, String>,
+    restore: RestoreTab,
+    revert: RevertTab,
+    history: HistoryTab,
+    job: Option<Job>,
+    options: RestoreOptions,
+    escalation: Escalation,
+}
+
+impl Dashboard {
+    fn draw<B: Backend>(&mut self, f: &mut Frame<B>) {
+        let size = f.size();
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Min(1)].as_ref())
+            .split(size);
+
+        let picking = match self.tab {
+            1 => self.restore.picking.is_some(),
+            2 => self.revert.picking.is_some(),
+            _ => false,
+        };
+        let keys = if picking { "Esc: Back  Tab/1-4: Switch" } else { "Tab/1-4: Switch" };
+        let tabs = Tabs::new(TABS.iter().map(|t| Spans::from(*t)).collect())
+            .block(Block::default().title(format!("🌀 Restitch  {}", keys)).borders(Borders::ALL))
+            .select(self.tab)
+            .highlight_style(Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED));
+        f.render_widget(tabs, chunks[0]);
+
+        match self.tab {
+            0 => match &mut self.export {
+                Ok(export) => export.draw(f, chunks[1]),
+                Err(e) => {
+                    let text = format!(
+                        "❌ {}\n\n💡 Fix {}, then run `restitch check` to confirm. The other tabs still work.",
+                        e.trim_start_matches("❌ "),
+                        self.config_path
+                    );
+                    let pane = Paragraph::new(text)
+                        .block(Block::default().title("🌀 Restitch: Select Configs").borders(Borders::ALL))
+                        .wrap(Wrap { trim: false });
+                    f.render_widget(pane, chunks[1]);
+                }
+            },
+            1 => self.restore.draw(f, chunks[1]),
+            2 => self.revert.draw(f, chunks[1]),
+            _ => self.history.draw(f, chunks[1]),
+        }
+
+        if let Some(job) = &mut self.job {
+            job.draw(f, size);
+        }
+    }
+
+    /// Hand an event to the open tab; keys it has no use for come back
+    fn handle(&mut self, event: Event) -> Option<KeyEvent> {
+        match self.tab {
+            0 => self.export_key(event),
+            1 => self.restore_key(event),
+            2 => self.revert_key(event),
+            _ => self.history.list.handle(event),
+        }
+    }
+
+    fn export_key(&mut self, event: Event) -> Option<KeyEvent> {
+        let key = match &mut self.export {
+            Ok(export) => export.handle(event)?,
+            Err(_) => key_of(event)?,
+        };
+        let (KeyCode::Char('p'), Ok(export)) = (key.code, &self.export) else {
+            return Some(key);
+        };
+        let items = export.picked();
+        self.job = Some(Job::start("Export", move || {
+            if items.is_empty() {
+                say!("⚠️ No config items selected. Nothing to export.");
+                return Ok(());
+            }
+            packager::create_archive(&items, "restitch-archive")
+        }));
+        None
+    }
+
+    fn restore_key(&mut self, event: Event) -> Option<KeyEvent> {
+        let tab = &mut self.restore;
+        let Some((archive, digest, picker)) = &mut tab.picking else {
+            let key = tab.list.handle(event)?;
+            if key.code != KeyCode::Enter {
+                return Some(key);
+            }
+            tab.open(&self.options);
+            return None;
+        };
+
+        let key = picker.handle(event)?;
+        match key.code {
+            KeyCode::Esc => tab.picking = None,
+            KeyCode::Enter => {
+                let archive = archive.to_string_lossy().to_string();
+                let (digest, items, options) = (digest.clone(), picker.items.clone(), self.options.clone());
+                self.job = Some(Job::start("Restore", move || {
+                    restore::restore_picked(&archive, digest, items, &options)
+                }));
+            }
+            _ => return Some(key),
+        }
+        None
+    }
+
+    fn revert_key(&mut self, event: Event) -> Option<KeyEvent> {
+        let tab = &mut self.revert;
+        let escalation = self.escalation;
+
+        if let Some((backup_path, picker)) = &mut tab.picking {
+            let key = picker.handle(event)?;
+            match key.code {
+                KeyCode::Esc => tab.picking = None,
+                KeyCode::Enter => {
+                    let (backup_path, steps) = (backup_path.clone(), picker.picked());
+                    self.job = Some(Job::start("Revert", move || {
+                        revert::revert_backup(&backup_path, &steps, escalation, false)
+                    }));
+                }
+                _ => return Some(key),
+            }
+            return None;
+        }
+
+        let key = tab.backups.handle(event)?;
+        let Some(backup_path) = tab.backups.selected().map(Path::to_path_buf) else {
+            return Some(key);
+        };
+        let target = self.options.target.clone();
+        match key.code {
+            KeyCode::Enter => {
+                self.job = Some(Job::start("Revert", move || {
+                    let steps = revert::plan_revert(&backup_path, &target)
+                        .map_err(|e| format!("❌ Could not read backup {}: {}", backup_path.display(), e))?;
+                    revert::revert_backup(&backup_path, &steps, escalation, false)
+                }));
+            }
+            KeyCode::Right | KeyCode::Char('l') => match revert::plan_revert(&backup_path, &target) {
+                Ok(steps) => tab.picking = Some((backup_path, RevertPicker::new(steps))),
+                Err(e) => {
+                    let message = format!("❌ Could not read backup {}: {}", backup_path.display(), e);
+                    self.job = Some(Job::start("Revert", move || Err(message)));
+                }
+            },
+            _ => return Some(key),
+        }
+        None
+    }
+
+    fn run<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> io::Result<()> {
+        loop {
+            // ⏳ A running job feeds its output and questions in between frames
+            if let Some((mut merges, reply)) = self.job.as_mut().and_then(Job::poll) {
+                merge::resolve_on(terminal, &mut merges)?;
+                let _ = reply.send(merges);
+            }
+            terminal.draw(|f| self.draw(f))?;
+
+            let Some(event) = ui::next_event()? else {
+                continue;
+            };
+            if let Some(job) = &mut self.job {
+                if job.handle(event) {
+                    // Whatever the job wrote shows up on the other tabs
+                    self.job = None;
+                    self.restore = RestoreTab::load();
+                    self.revert = RevertTab::load();
+                    self.history = HistoryTab::load();
+                }
+                continue;
+            }
+
+            let Some(key) = self.handle(event) else {
+                continue;
+            };
+            match key.code {
+                KeyCode::Char('q') => return Ok(()),
+                KeyCode::Tab => self.tab = (self.tab + 1) % TABS.len(),
+                KeyCode::BackTab => self.tab = (self.tab + TABS.len() - 1) % TABS.len(),
+                KeyCode::Char(c @ '1'..='4') => self.tab = c as usize - '1' as usize,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Run the tabbed dashboard until the user quits
+pub fn run(config_path: &str, selection: &Selection, options: &RestoreOptions) -> io::Result<()> {
+    let export = ExportScreen::load(config_path, selection);
+
+    // sudo and pkexec would ask for a password behind the dashboard, so only root writes system-level items here
+    let options = RestoreOptions { no_escalate: true, ..options.clone() };
+    let mut dashboard = Dashboard {
+        tab: 0,
+        config_path: config_path.to_string(),
+        export,
+        restore: RestoreTab::load(),
+        revert: RevertTab::load(),
+        history: HistoryTab::load(),
+        job: None,
+        options,
+        escalation: Escalation::detect(false),
+    };
+
+    let mut terminal = TerminalGuard::enter()?;
+    let result = dashboard.run(&mut terminal);
+    drop(terminal);
+
+    if let Ok(export) = &dashboard.export {
+        export.print_skipped();
+    }
+    result
+}
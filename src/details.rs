@@ -0,0 +1,322 @@
+// details.rs
+
+use crate::backup;
+use crate::config::ConfigItem;
+use crate::merge;
+use crate::packager;
+use crate::paths;
+
+use chrono::{DateTime, Local};
+use glob::Pattern;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::SystemTime;
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+};
+
+/// Most entries listed in a target's file tree
+const MAX_TREE_LINES: usize = 100;
+/// Most lines of a text file kept for the preview
+const MAX_PREVIEW_LINES: usize = 200;
+
+/// What the detail pane shows about one target
+pub struct Details {
+    pub size: u64,
+    pub files: u64,
+    /// Newest modification time of anything in the target
+    pub modified: Option<SystemTime>,
+    pub tree: Vec<String>,
+    /// The target itself if it is a text file, otherwise its first text file
+    pub preview: Option<(String, Vec<Spans<'static>>)>,
+}
+
+impl Details {
+    pub fn modified_label(&self) -> String {
+        self.modified
+            .map(|t| DateTime::<Local>::from(t).format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|| "unknown".to_string())
+    }
+}
+
+/// Walk a target once, the way the packager would, and gather everything for the pane
+fn inspect(item: &ConfigItem) -> Details {
+    let source = paths::expand(&item.path);
+    let exclude: Vec<Pattern> = item.exclude.iter().filter_map(|p| Pattern::new(p).ok()).collect();
+    let mut details = Details { size: 0, files: 0, modified: None, tree: vec![], preview: None };
+    let mut shown = 0;
+
+    let walker = walkdir::WalkDir::new(&source)
+        .follow_links(true)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| !packager::is_excluded(e.path().strip_prefix(&source).unwrap_or(Path::new("")), &exclude));
+    for entry in walker.filter_map(Result::ok) {
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        if let Ok(modified) = meta.modified() {
+            details.modified = Some(details.modified.map_or(modified, |newest| newest.max(modified)));
+        }
+        if meta.is_file() {
+            details.files += 1;
+            details.size += meta.len();
+            if details.preview.is_none() {
+                details.preview = merge::read_text(entry.path()).map(|text| {
+                    let name = entry.path().strip_prefix(&source).unwrap_or(entry.path());
+                    let name = if name.as_os_str().is_empty() { item.path.clone() } else { name.display().to_string() };
+                    (name, highlight(entry.path(), &text))
+                });
+            }
+        }
+
+        if entry.depth() == 0 {
+            continue;
+        }
+        shown += 1;
+        if shown > MAX_TREE_LINES {
+            continue;
+        }
+        let indent = "  ".repeat(entry.depth() - 1);
+        let name = entry.file_name().to_string_lossy();
+        if meta.is_dir() {
+            details.tree.push(format!("{}📁 {}/", indent, name));
+        } else {
+            details.tree.push(format!("{}{} ({})", indent, name, backup::format_size(meta.len())));
+        }
+    }
+    if shown > MAX_TREE_LINES {
+        details.tree.push(format!("… {} more", shown - MAX_TREE_LINES));
+    }
+    details
+}
+
+/// Targets waiting to be inspected; the newest request is served first
+#[derive(Default)]
+struct Queue {
+    jobs: Vec<ConfigItem>,
+    closed: bool,
+}
+
+/// Works out `Details` on a background thread, so a 2 GB target never stalls the UI
+pub struct DetailWorker {
+    queue: Arc<(Mutex<Queue>, Condvar)>,
+    results: Receiver<(String, Details)>,
+    /// By target path; `None` while the worker is still on it
+    cache: HashMap<String, Option<Details>>,
+}
+
+impl DetailWorker {
+    pub fn spawn() -> Self {
+        let queue = Arc::new((Mutex::new(Queue::default()), Condvar::new()));
+        let (sender, results) = mpsc::channel();
+        let shared = Arc::clone(&queue);
+        thread::spawn(move || work(&shared, &sender));
+        DetailWorker { queue, results, cache: HashMap::new() }
+    }
+
+    fn collect(&mut self) {
+        while let Ok((path, details)) = self.results.try_recv() {
+            self.cache.insert(path, Some(details));
+        }
+    }
+
+    /// Details of a target, or `None` while they are being worked out
+    pub fn get(&mut self, item: &ConfigItem) -> Option<&Details> {
+        self.collect();
+        if !self.cache.contains_key(&item.path) {
+            self.cache.insert(item.path.clone(), None);
+            let (lock, ready) = &*self.queue;
+            lock.lock().unwrap().jobs.push(item.clone());
+            ready.notify_one();
+        }
+        self.cache[&item.path].as_ref()
+    }
+}
+
+impl DetailWorker {
+    /// Work a target out again the next time it is asked for, after it was edited
+    pub fn forget(&mut self, path: &str) {
+        self.collect();
+        self.cache.remove(path);
+    }
+
+    /// Work every target out again, after files changed on disk
+    pub fn refresh(&mut self) {
+        self.collect();
+        self.cache.clear();
+    }
+}
+
+impl Drop for DetailWorker {
+    fn drop(&mut self) {
+        let (lock, ready) = &*self.queue;
+        lock.lock().unwrap().closed = true;
+        ready.notify_one();
+    }
+}
+
+fn work(queue: &(Mutex<Queue>, Condvar), results: &Sender<(String, Details)>) {
+    let (lock, ready) = queue;
+    loop {
+        let item = {
+            let mut queue = lock.lock().unwrap();
+            while queue.jobs.is_empty() && !queue.closed {
+                queue = ready.wait(queue).unwrap();
+            }
+            if queue.closed {
+                return;
+            }
+            queue.jobs.pop().expect("queue is not empty")
+        };
+        let details = inspect(&item);
+        if results.send((item.path, details)).is_err() {
+            return;
+        }
+    }
+}
+
+/// How a file's lines are coloured, picked from its name
+struct Syntax {
+    /// Line comment markers, recognized at the start of a line or after whitespace
+    comments: &'static [&'static str],
+    keywords: &'static [&'static str],
+}
+
+const SHELL: Syntax = Syntax {
+    comments: &["#"],
+    keywords: &[
+        "if", "then", "else", "elif", "fi", "for", "while", "do", "done", "case", "esac", "function", "export",
+        "alias", "source", "local", "return", "in", "set", "unset",
+    ],
+};
+const LUA: Syntax = Syntax {
+    comments: &["--"],
+    keywords: &[
+        "local", "function", "end", "if", "then", "else", "elseif", "for", "while", "do", "return", "require", "nil",
+        "true", "false", "and", "or", "not", "in",
+    ],
+};
+const VIM: Syntax = Syntax {
+    comments: &["\""],
+    keywords: &[
+        "set", "let", "if", "else", "endif", "function", "endfunction", "call", "map", "nmap", "nnoremap", "inoremap",
+        "vnoremap", "autocmd", "augroup", "syntax", "colorscheme", "source",
+    ],
+};
+const PYTHON: Syntax = Syntax {
+    comments: &["#"],
+    keywords: &[
+        "def", "class", "import", "from", "return", "if", "elif", "else", "for", "while", "in", "and", "or", "not",
+        "None", "True", "False", "with", "as",
+    ],
+};
+const C_LIKE: Syntax = Syntax {
+    comments: &["//"],
+    keywords: &[
+        "fn", "let", "const", "var", "function", "return", "if", "else", "for", "while", "import", "export", "true",
+        "false", "null",
+    ],
+};
+/// TOML, INI, YAML, and most `key = value` config files
+const CONFIG: Syntax = Syntax { comments: &["#", ";"], keywords: &["true", "false", "yes", "no", "on", "off"] };
+
+const SHELL_FILES: &[&str] = &[".bashrc", ".zshrc", ".profile", ".bash_profile", ".zprofile", ".zshenv", ".xinitrc"];
+
+fn syntax_for(path: &Path) -> &'static Syntax {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    match extension.as_str() {
+        "sh" | "bash" | "zsh" | "fish" => &SHELL,
+        "lua" => &LUA,
+        "vim" => &VIM,
+        "py" => &PYTHON,
+        "js" | "ts" | "json" | "jsonc" | "rs" | "c" | "h" | "css" | "kdl" => &C_LIKE,
+        _ if name.ends_with("rc") && name.starts_with('.') && name.contains("vim") => &VIM,
+        _ if SHELL_FILES.contains(&name.as_str()) => &SHELL,
+        _ => &CONFIG,
+    }
+}
+
+/// Colour the first lines of a text file for the preview
+fn highlight(path: &Path, text: &str) -> Vec<Spans<'static>> {
+    let syntax = syntax_for(path);
+    text.lines().take(MAX_PREVIEW_LINES).map(|line| highlight_line(line, syntax)).collect()
+}
+
+fn highlight_line(line: &str, syntax: &Syntax) -> Spans<'static> {
+    let comment = Style::default().fg(Color::DarkGray);
+    let string = Style::default().fg(Color::Green);
+    let number = Style::default().fg(Color::Yellow);
+    let keyword = Style::default().fg(Color::Magenta);
+    let key = Style::default().fg(Color::Cyan);
+
+    let trimmed = line.trim();
+    if trimmed.starts_with('[') && trimmed.ends_with(']') {
+        return Spans::from(Span::styled(line.to_string(), key.add_modifier(Modifier::BOLD)));
+    }
+
+    let chars: Vec<char> = line.chars().collect();
+    let indent = chars.iter().take_while(|c| c.is_whitespace()).count();
+    let offsets: Vec<usize> = line.char_indices().map(|(offset, _)| offset).collect();
+    let mut spans = vec![];
+    let mut plain = String::new();
+    let mut i = 0;
+    let flush = |plain: &mut String, spans: &mut Vec<Span<'static>>| {
+        if !plain.is_empty() {
+            spans.push(Span::raw(std::mem::take(plain)));
+        }
+    };
+    while i < chars.len() {
+        let c = chars[i];
+        let leading = i <= indent;
+        let at_boundary = i == 0 || chars[i - 1].is_whitespace();
+        let rest = &line[offsets[i]..];
+
+        // A quote only starts a comment at the beginning of a line, as in vim
+        let marker = syntax.comments.iter().find(|marker| rest.starts_with(*marker));
+        if marker.is_some_and(|marker| leading || (at_boundary && *marker != "\"")) {
+            flush(&mut plain, &mut spans);
+            spans.push(Span::styled(rest.to_string(), comment));
+            break;
+        }
+        if c == '"' || c == '\'' {
+            let end = chars[i + 1..].iter().position(|&q| q == c).map_or(chars.len(), |p| i + 2 + p);
+            flush(&mut plain, &mut spans);
+            spans.push(Span::styled(chars[i..end].iter().collect::<String>(), string));
+            i = end;
+            continue;
+        }
+        if c.is_alphanumeric() || c == '_' {
+            let end = chars[i..].iter().position(|&w| !(w.is_alphanumeric() || matches!(w, '_' | '-' | '.'))).map_or(chars.len(), |p| i + p);
+            let word: String = chars[i..end].iter().collect();
+            let assigned = chars[end..].iter().find(|w| !w.is_whitespace()).is_some_and(|&w| w == '=' || w == ':');
+            let style = if c.is_ascii_digit() {
+                Some(number)
+            } else if syntax.keywords.contains(&word.as_str()) {
+                Some(keyword)
+            } else if leading && assigned {
+                Some(key)
+            } else {
+                None
+            };
+            match style {
+                Some(style) => {
+                    flush(&mut plain, &mut spans);
+                    spans.push(Span::styled(word, style));
+                }
+                None => plain.push_str(&word),
+            }
+            i = end;
+            continue;
+        }
+        plain.push(c);
+        i += 1;
+    }
+    flush(&mut plain, &mut spans);
+    Spans::from(spans)
+}
@@ -1,25 +1,116 @@
-use crate::config::ConfigItem;
+use crate::config::{ConfigItem, ConflictPolicy, Profile};
+use crate::host::When;
+use crate::paths;
+use crate::prune::RetentionPolicy;
+use crate::safety::RestoreLimits;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use serde::Deserialize;
+use toml_edit::{value, Array, ArrayOfTables, DocumentMut, Item, Table};
 
 #[derive(Debug, Deserialize)]
 struct ConfigFileEntry {
     name: String,
     path: String,
+    #[serde(default)]
+    policy: Option<ConflictPolicy>,
+    #[serde(default)]
+    exclude: Vec<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    when: Option<When>,
+    #[serde(default)]
+    template: bool,
 }
 
 /// This tells Serde to expect multiple [[config]] tables instead of a nested array field.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Default)]
 struct ConfigFile {
-    #[serde(rename = "config")]
+    /// Other targets files layered underneath this one, in order
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default, rename = "config")]
     configs: Vec<ConfigFileEntry>,
+    retention: Option<RetentionPolicy>,
+    restore: Option<RestoreLimits>,
+    #[serde(default)]
+    profile: BTreeMap<String, Profile>,
+}
+
+impl ConfigFile {
+    /// Lay `over` on top: entries with the same name and whole tables are replaced
+    fn layer(&mut self, over: ConfigFile) {
+        for entry in over.configs {
+            match self.configs.iter_mut().find(|e| e.name == entry.name) {
+                Some(existing) => *existing = entry,
+                None => self.configs.push(entry),
+            }
+        }
+        self.retention = over.retention.or(self.retention.take());
+        self.restore = over.restore.or(self.restore.take());
+        self.profile.extend(over.profile);
+    }
 }
 
-/// Expand tilde and check if path exists
+fn parse_config_file(config_path: &str) -> Result<ConfigFile, String> {
+    load_layered(Path::new(config_path), &mut vec![])
+}
+
+/// Read a targets file on top of everything it includes; `stack` holds the files being read, to catch cycles
+fn load_layered(path: &Path, stack: &mut Vec<PathBuf>) -> Result<ConfigFile, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|_| format!("❌ Could not read config file at '{}'", path.display()))?;
+    let mut file: ConfigFile = toml::from_str(&content)
+        .map_err(|e| format!("❌ Failed to parse config file at '{}': {}", path.display(), e))?;
+
+    let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if stack.contains(&canonical) {
+        return Err(format!("❌ Include cycle: '{}' includes itself", path.display()));
+    }
+
+    for entry in &mut file.configs {
+        entry.path = paths::expand_vars(&entry.path)
+            .map_err(|e| format!("❌ Entry '{}' in '{}': {}", entry.name, path.display(), e))?;
+    }
+
+    stack.push(canonical);
+    let mut layered = ConfigFile::default();
+    for pattern in std::mem::take(&mut file.include) {
+        for included in resolve_include(path, &pattern)? {
+            layered.layer(load_layered(&included, stack)?);
+        }
+    }
+    stack.pop();
+
+    layered.layer(file);
+    Ok(layered)
+}
+
+/// Why `content` does not deserialize as a targets file, if it does not
+pub fn type_error(content: &str) -> Option<toml::de::Error> {
+    toml::from_str::<ConfigFile>(content).err()
+}
+
+/// Files named by one `include` pattern, relative to the including file; globs expand in sorted order
+pub fn resolve_include(from: &Path, pattern: &str) -> Result<Vec<PathBuf>, String> {
+    let expanded = paths::expand_vars(pattern)
+        .map_err(|e| format!("❌ Include in '{}': {}", from.display(), e))?;
+    let base = from.parent().unwrap_or(Path::new(""));
+    let full = base.join(&expanded);
+
+    if !expanded.contains(['*', '?', '[']) {
+        return Ok(vec![full]);
+    }
+    let matches = glob::glob(&full.to_string_lossy())
+        .map_err(|e| format!("❌ Invalid include pattern '{}' in '{}': {}", pattern, from.display(), e))?;
+    Ok(matches.filter_map(Result::ok).collect())
+}
+
+/// Expand tilde and XDG variables and check if path exists
 fn expand_and_check(path: &str) -> Option<PathBuf> {
-    let expanded = shellexpand::tilde(path).into_owned();
-    let pathbuf = PathBuf::from(&expanded);
+    let pathbuf = paths::expand(path);
     if pathbuf.exists() {
         Some(pathbuf)
     } else {
@@ -27,21 +118,222 @@ fn expand_and_check(path: &str) -> Option<PathBuf> {
     }
 }
 
-/// Load targets from a TOML config file
-pub fn scan_targets_from_file(config_path: &str) -> Result<Vec<ConfigItem>, String> {
-    let content = fs::read_to_string(config_path)
-        .map_err(|_| format!("❌ Could not read config file at '{}'", config_path))?;
+/// A target left out on this machine, and why
+pub struct Skipped {
+    pub name: String,
+    pub reason: String,
+}
+
+/// Load targets from a TOML config file, leaving out those whose `when` fails or whose path is missing
+pub fn scan_targets_from_file(config_path: &str) -> Result<(Vec<ConfigItem>, Vec<Skipped>), String> {
+    let parsed = parse_config_file(config_path)?;
+    let mut items = vec![];
+    let mut skipped = vec![];
 
-    let parsed: ConfigFile = toml::from_str(&content)
-        .map_err(|_| format!("❌ Failed to parse config file at '{}'", config_path))?;
+    for entry in &parsed.configs {
+        let applies = entry.when.as_ref().map_or(Ok(()), When::check);
+        let reason = match applies {
+            Err(reason) => reason,
+            Ok(()) if expand_and_check(&entry.path).is_none() => "path does not exist".to_string(),
+            Ok(()) => {
+                // Keep the portable form; the real location is resolved on the restoring machine
+                items.push(ConfigItem {
+                    name: entry.name.clone(),
+                    path: paths::portable(&entry.path),
+                    selected: true,
+                    policy: entry.policy,
+                    owner: None,
+                    exclude: entry.exclude.clone(),
+                    tags: entry.tags.clone(),
+                    template: entry.template,
+                });
+                continue;
+            }
+        };
+        skipped.push(Skipped { name: entry.name.clone(), reason });
+    }
 
-    Ok(parsed.configs.iter()
-        .filter_map(|entry| {
-            expand_and_check(&entry.path).map(|abs_path| ConfigItem {
-                name: entry.name.clone(),
-                path: abs_path.to_string_lossy().to_string(),
-                selected: true,
-            })
+    Ok((items, skipped))
+}
+
+/// Load the `[retention]` table from a TOML config file
+pub fn load_retention(config_path: &str) -> Result<RetentionPolicy, String> {
+    Ok(parse_config_file(config_path)?.retention.unwrap_or_default())
+}
+
+/// Look up a `[profile.<name>]` table
+pub fn load_profile(config_path: &str, name: &str) -> Result<Profile, String> {
+    let mut profiles = parse_config_file(config_path)?.profile;
+    profiles.remove(name).ok_or_else(|| {
+        let known: Vec<&String> = profiles.keys().collect();
+        if known.is_empty() {
+            format!("❌ Unknown profile '{}': '{}' defines no profiles", name, config_path)
+        } else {
+            format!(
+                "❌ Unknown profile '{}' (defined: {})",
+                name,
+                known.iter().map(|k| k.as_str()).collect::<Vec<_>>().join(", ")
+            )
+        }
+    })
+}
+
+/// Load the `[restore]` table; system-level targets listed in the file are allowed roots too
+pub fn load_restore_limits(config_path: &str) -> Result<RestoreLimits, String> {
+    let parsed = parse_config_file(config_path)?;
+    let mut limits = parsed.restore.unwrap_or_default();
+    limits.allowed_roots.extend(
+        parsed.configs
+            .into_iter()
+            .filter(|entry| paths::is_system(&entry.path))
+            .map(|entry| entry.path),
+    );
+    Ok(limits)
+}
+
+/// A well-known application and where it keeps its configuration
+pub struct KnownApp {
+    pub name: &'static str,
+    /// Candidate locations; every one that exists becomes a target
+    pub paths: &'static [&'static str],
+    /// Caches, state and secrets inside those locations that are not worth exporting
+    pub exclude: &'static [&'static str],
+}
+
+/// Built-in catalog used by `restitch detect`
+pub const CATALOG: &[KnownApp] = &[
+    // 🐚 Shells
+    KnownApp { name: "Bash", paths: &["~/.bashrc", "~/.bash_profile", "~/.bash_aliases", "~/.profile"], exclude: &[] },
+    KnownApp { name: "Zsh", paths: &["~/.zshrc", "~/.zshenv", "~/.zprofile", "~/.p10k.zsh"], exclude: &[] },
+    KnownApp { name: "Oh My Zsh Custom", paths: &["~/.oh-my-zsh/custom"], exclude: &["plugins/*/.git", "themes/*/.git"] },
+    KnownApp { name: "Fish", paths: &["$XDG_CONFIG_HOME/fish"], exclude: &["fish_history"] },
+    KnownApp { name: "Nushell", paths: &["$XDG_CONFIG_HOME/nushell"], exclude: &["history.txt", "history.sqlite3*"] },
+    KnownApp { name: "Starship", paths: &["$XDG_CONFIG_HOME/starship.toml"], exclude: &[] },
+    // ✍️ Editors
+    KnownApp { name: "Vim", paths: &["~/.vimrc", "~/.vim"], exclude: &["undo", "swap", "backup", "plugged", ".netrwhist"] },
+    KnownApp { name: "Neovim", paths: &["$XDG_CONFIG_HOME/nvim"], exclude: &["plugin/packer_compiled.lua"] },
+    KnownApp { name: "Helix", paths: &["$XDG_CONFIG_HOME/helix"], exclude: &["runtime"] },
+    KnownApp { name: "Emacs", paths: &["~/.emacs", "~/.emacs.d", "$XDG_CONFIG_HOME/emacs"], exclude: &["elpa", "eln-cache", "auto-save-list", "straight", "*.elc"] },
+    KnownApp { name: "VS Code", paths: &["$XDG_CONFIG_HOME/Code/User"], exclude: &["workspaceStorage", "globalStorage", "History", "*.log"] },
+    KnownApp { name: "VSCodium", paths: &["$XDG_CONFIG_HOME/VSCodium/User"], exclude: &["workspaceStorage", "globalStorage", "History", "*.log"] },
+    KnownApp { name: "Micro", paths: &["$XDG_CONFIG_HOME/micro"], exclude: &["buffers", "backups"] },
+    // 🖥️ Terminals
+    KnownApp { name: "Kitty", paths: &["$XDG_CONFIG_HOME/kitty"], exclude: &[] },
+    KnownApp { name: "Alacritty", paths: &["$XDG_CONFIG_HOME/alacritty", "~/.alacritty.toml", "~/.alacritty.yml"], exclude: &[] },
+    KnownApp { name: "WezTerm", paths: &["$XDG_CONFIG_HOME/wezterm", "~/.wezterm.lua"], exclude: &[] },
+    KnownApp { name: "Foot", paths: &["$XDG_CONFIG_HOME/foot"], exclude: &[] },
+    KnownApp { name: "Ghostty", paths: &["$XDG_CONFIG_HOME/ghostty"], exclude: &[] },
+    KnownApp { name: "Tmux", paths: &["~/.tmux.conf", "$XDG_CONFIG_HOME/tmux"], exclude: &["plugins", "resurrect"] },
+    KnownApp { name: "Zellij", paths: &["$XDG_CONFIG_HOME/zellij"], exclude: &[] },
+    // 🪟 Window managers and desktops
+    KnownApp { name: "i3", paths: &["$XDG_CONFIG_HOME/i3", "~/.i3"], exclude: &[] },
+    KnownApp { name: "i3status", paths: &["$XDG_CONFIG_HOME/i3status"], exclude: &[] },
+    KnownApp { name: "Sway", paths: &["$XDG_CONFIG_HOME/sway"], exclude: &[] },
+    KnownApp { name: "Hyprland", paths: &["$XDG_CONFIG_HOME/hypr"], exclude: &[] },
+    KnownApp { name: "Waybar", paths: &["$XDG_CONFIG_HOME/waybar"], exclude: &[] },
+    KnownApp { name: "Polybar", paths: &["$XDG_CONFIG_HOME/polybar"], exclude: &[] },
+    KnownApp { name: "Rofi", paths: &["$XDG_CONFIG_HOME/rofi"], exclude: &[] },
+    KnownApp { name: "Dunst", paths: &["$XDG_CONFIG_HOME/dunst"], exclude: &[] },
+    KnownApp { name: "Picom", paths: &["$XDG_CONFIG_HOME/picom", "$XDG_CONFIG_HOME/picom.conf"], exclude: &[] },
+    KnownApp { name: "bspwm", paths: &["$XDG_CONFIG_HOME/bspwm", "$XDG_CONFIG_HOME/sxhkd"], exclude: &[] },
+    KnownApp { name: "Openbox", paths: &["$XDG_CONFIG_HOME/openbox"], exclude: &[] },
+    KnownApp { name: "XFCE", paths: &["$XDG_CONFIG_HOME/xfce4"], exclude: &["xfce4-session", "desktop/icons*"] },
+    KnownApp {
+        name: "KDE Plasma",
+        paths: &[
+            "$XDG_CONFIG_HOME/kdeglobals",
+            "$XDG_CONFIG_HOME/kwinrc",
+            "$XDG_CONFIG_HOME/kglobalshortcutsrc",
+            "$XDG_CONFIG_HOME/plasma-org.kde.plasma.desktop-appletsrc",
+            "$XDG_CONFIG_HOME/plasmashellrc",
+            "$XDG_CONFIG_HOME/konsolerc",
+        ],
+        exclude: &[],
+    },
+    KnownApp { name: "GTK 3", paths: &["$XDG_CONFIG_HOME/gtk-3.0"], exclude: &[] },
+    KnownApp { name: "GTK 4", paths: &["$XDG_CONFIG_HOME/gtk-4.0"], exclude: &[] },
+    KnownApp { name: "X Session", paths: &["~/.xinitrc", "~/.Xresources", "~/.xprofile"], exclude: &[] },
+    KnownApp { name: "Fonts", paths: &["$XDG_DATA_HOME/fonts", "~/.fonts"], exclude: &[] },
+    KnownApp { name: "Themes", paths: &["~/.themes"], exclude: &[] },
+    // 🛠️ Development
+    KnownApp { name: "Git", paths: &["~/.gitconfig", "$XDG_CONFIG_HOME/git"], exclude: &["credentials"] },
+    KnownApp { name: "SSH", paths: &["~/.ssh/config"], exclude: &[] },
+    KnownApp { name: "GnuPG", paths: &["~/.gnupg/gpg.conf", "~/.gnupg/gpg-agent.conf"], exclude: &[] },
+    KnownApp { name: "Cargo", paths: &["~/.cargo/config.toml"], exclude: &[] },
+    KnownApp { name: "npm", paths: &["~/.npmrc"], exclude: &[] },
+    KnownApp { name: "Readline", paths: &["~/.inputrc"], exclude: &[] },
+    KnownApp { name: "htop", paths: &["$XDG_CONFIG_HOME/htop"], exclude: &[] },
+    KnownApp { name: "Ranger", paths: &["$XDG_CONFIG_HOME/ranger"], exclude: &["bookmarks", "history", "tagged"] },
+    KnownApp { name: "lf", paths: &["$XDG_CONFIG_HOME/lf"], exclude: &[] },
+    KnownApp { name: "mpv", paths: &["$XDG_CONFIG_HOME/mpv"], exclude: &["watch_later"] },
+];
+
+/// A catalog entry with the candidate paths found on this machine
+pub struct Detected {
+    pub app: &'static KnownApp,
+    pub found: Vec<&'static str>,
+}
+
+/// Check every catalog entry against this machine
+pub fn detect_installed() -> Vec<Detected> {
+    CATALOG
+        .iter()
+        .map(|app| Detected {
+            app,
+            found: app.paths.iter().copied().filter(|p| expand_and_check(p).is_some()).collect(),
         })
-        .collect())
+        .collect()
+}
+
+/// A starter targets file for everything that was found
+pub fn starter_targets(detected: &[Detected]) -> String {
+    let mut entries = ArrayOfTables::new();
+
+    for d in detected {
+        for path in &d.found {
+            let name = if d.found.len() == 1 {
+                d.app.name.to_string()
+            } else {
+                let file = path.rsplit('/').next().unwrap_or(path);
+                format!("{} ({})", d.app.name, file)
+            };
+            let mut entry = Table::new();
+            entry.decor_mut().set_prefix("\n");
+            entry.insert("name", value(name));
+            entry.insert("path", value(*path));
+
+            // Excludes only make sense below a directory
+            if !d.app.exclude.is_empty() && paths::expand(path).is_dir() {
+                entry.insert("exclude", value(d.app.exclude.iter().copied().collect::<Array>()));
+            }
+            entries.push(entry);
+        }
+    }
+
+    let mut doc = DocumentMut::new();
+    doc.insert("config", Item::ArrayOfTables(entries));
+    format!("# Generated by `restitch detect`; edit freely.\n{}", doc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static ODD: KnownApp = KnownApp { name: "Odd \"App\"", paths: &[], exclude: &["cache\\*", "say \"hi\""] };
+
+    #[test]
+    fn starter_targets_escape_names_paths_and_excludes() {
+        let text = starter_targets(&[Detected { app: &ODD, found: vec!["/", "~/.odd \"rc\""] }]);
+        assert_eq!(
+            text,
+            "# Generated by `restitch detect`; edit freely.\n\
+             \n[[config]]\nname = 'Odd \"App\" ()'\npath = \"/\"\nexclude = ['cache\\*', 'say \"hi\"']\n\
+             \n[[config]]\nname = 'Odd \"App\" (.odd \"rc\")'\npath = '~/.odd \"rc\"'\n"
+        );
+
+        let doc: toml::Table = toml::from_str(&text).unwrap();
+        let entries = doc["config"].as_array().unwrap();
+        assert_eq!(entries[1]["path"].as_str(), Some("~/.odd \"rc\""));
+        assert_eq!(entries[0]["exclude"][0].as_str(), Some("cache\\*"));
+    }
 }
@@ -0,0 +1,229 @@
+// editor.rs
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use toml_edit::{value, Array, ArrayOfTables, DocumentMut, Item, Table, Value};
+
+/// The targets file as an editable document; comments and layout survive the edits
+pub struct TargetsFile {
+    path: PathBuf,
+    doc: DocumentMut,
+}
+
+impl TargetsFile {
+    /// Open a targets file for editing; a missing file starts out empty
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("could not read '{}': {}", path.display(), e)),
+        };
+        let doc = content
+            .parse::<DocumentMut>()
+            .map_err(|e| format!("could not parse '{}': {}", path.display(), e.message().trim()))?;
+        Ok(TargetsFile { path: path.to_path_buf(), doc })
+    }
+
+    /// Write the document next to the targets file, then move it into place,
+    /// so a failed write never leaves the file half written
+    pub fn save(&self) -> io::Result<()> {
+        // Through a symlink, the file it points at is the one replaced
+        let path = fs::canonicalize(&self.path).unwrap_or_else(|_| self.path.clone());
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let mut staged = tempfile::NamedTempFile::new_in(dir)?;
+        staged.write_all(self.doc.to_string().as_bytes())?;
+        staged.as_file().sync_all()?;
+        if let Ok(meta) = fs::metadata(&path) {
+            staged.as_file().set_permissions(meta.permissions())?;
+        }
+        staged.persist(&path).map_err(|e| e.error)?;
+        Ok(())
+    }
+
+    /// The `[[config]]` tables; an empty inline `config = []` is turned into them
+    fn entries(&mut self) -> Result<&mut ArrayOfTables, String> {
+        let empty_inline = self.doc.get("config").is_some_and(|item| item.as_array().is_some_and(Array::is_empty));
+        if empty_inline || !self.doc.contains_key("config") {
+            self.doc.insert("config", Item::ArrayOfTables(ArrayOfTables::new()));
+        }
+        self.doc
+            .get_mut("config")
+            .and_then(Item::as_array_of_tables_mut)
+            .ok_or_else(|| "`config` is written as an inline array; edit it by hand".to_string())
+    }
+
+    fn position(&mut self, name: &str) -> Result<usize, String> {
+        let path = self.path.display().to_string();
+        self.entries()?
+            .iter()
+            .position(|table| table.get("name").and_then(Item::as_str) == Some(name))
+            .ok_or_else(|| format!("'{}' is not defined in {} (it may come from an included file)", name, path))
+    }
+
+    fn entry(&mut self, name: &str) -> Result<&mut Table, String> {
+        let idx = self.position(name)?;
+        Ok(self.entries()?.get_mut(idx).expect("position is in range"))
+    }
+
+    fn ensure_unused(&mut self, name: &str) -> Result<(), String> {
+        match self.position(name) {
+            Ok(_) => Err(format!("an entry named '{}' already exists", name)),
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// Whatever is written above an empty inline `config = []`
+    fn inline_comment(&self) -> Option<String> {
+        let table = self.doc.as_table();
+        table.get("config")?.as_array().filter(|array| array.is_empty())?;
+        Some(table.key("config")?.leaf_decor().prefix()?.as_str()?.to_string())
+    }
+
+    pub fn add(&mut self, name: &str, path: &str) -> Result<(), String> {
+        // Turning `config = []` into tables would drop the comment above it, so it moves to the new entry
+        let comment = self.inline_comment();
+        self.ensure_unused(name)?;
+        let mut table = Table::new();
+        if let Some(comment) = comment {
+            table.decor_mut().set_prefix(comment);
+        }
+        table.insert("name", value(name));
+        table.insert("path", value(path));
+        self.entries()?.push(table);
+        Ok(())
+    }
+
+    /// Rename an entry, along with any profile that picks it by name
+    pub fn rename(&mut self, old: &str, new: &str) -> Result<(), String> {
+        self.ensure_unused(new)?;
+        set_keeping_decor(self.entry(old)?, "name", Value::from(new));
+
+        if let Some(profiles) = self.doc.get_mut("profile").and_then(Item::as_table_like_mut) {
+            for (_, profile) in profiles.iter_mut() {
+                let Some(names) = profile.get_mut("names").and_then(Item::as_array_mut) else {
+                    continue;
+                };
+                for name in names.iter_mut() {
+                    if name.as_str() == Some(old) {
+                        let decor = name.decor().clone();
+                        *name = Value::from(new);
+                        *name.decor_mut() = decor;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<(), String> {
+        let idx = self.position(name)?;
+        self.entries()?.remove(idx);
+        Ok(())
+    }
+
+    pub fn set_tags(&mut self, name: &str, tags: &[String]) -> Result<(), String> {
+        let entry = self.entry(name)?;
+        if tags.is_empty() {
+            entry.remove("tags");
+        } else {
+            set_keeping_decor(entry, "tags", Value::Array(tags.iter().map(String::as_str).collect()));
+        }
+        Ok(())
+    }
+}
+
+/// Replace a value but keep the whitespace and trailing comment around the old one
+fn set_keeping_decor(table: &mut Table, key: &str, mut new: Value) {
+    if let Some(old) = table.get(key).and_then(Item::as_value) {
+        *new.decor_mut() = old.decor().clone();
+    }
+    // Indexing keeps the key and its position, where `insert` would replace them
+    table[key] = Item::Value(new);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGETS: &str = r#"# My targets
+[[config]]
+name = "Bash" # the shell
+path = "~/.bashrc"
+tags = ["shell"]   # keep
+
+# Editors
+[[config]]
+name = "Vim"
+path = "~/.vimrc"
+
+[profile.work]
+names = ["Bash", "Vim"] # both
+"#;
+
+    fn edited(content: &str, edit: impl FnOnce(&mut TargetsFile)) -> String {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config_targets.toml");
+        fs::write(&path, content).unwrap();
+        let mut file = TargetsFile::open(&path).unwrap();
+        edit(&mut file);
+        file.save().unwrap();
+        fs::read_to_string(&path).unwrap()
+    }
+
+    #[test]
+    fn add_keeps_comments_and_layout() {
+        let text = edited(TARGETS, |file| file.add("Git", "~/.gitconfig").unwrap());
+        assert!(text.starts_with(TARGETS.split("[profile.work]").next().unwrap()));
+        assert!(text.contains("names = [\"Bash\", \"Vim\"] # both"));
+        assert!(text.contains("[[config]]\nname = \"Git\"\npath = \"~/.gitconfig\"\n"));
+    }
+
+    #[test]
+    fn rename_updates_profiles_and_keeps_comments() {
+        let text = edited(TARGETS, |file| file.rename("Bash", "Zsh").unwrap());
+        assert_eq!(text, TARGETS.replace("\"Bash\"", "\"Zsh\""));
+    }
+
+    #[test]
+    fn remove_leaves_the_rest_alone() {
+        let text = edited(TARGETS, |file| file.remove("Vim").unwrap());
+        assert!(!text.contains("~/.vimrc"));
+        assert!(text.starts_with("# My targets\n[[config]]\nname = \"Bash\" # the shell\n"));
+        assert!(text.contains("[profile.work]\nnames = [\"Bash\", \"Vim\"] # both\n"));
+    }
+
+    #[test]
+    fn set_tags_keeps_the_trailing_comment() {
+        let text = edited(TARGETS, |file| file.set_tags("Bash", &["shell".into(), "core".into()]).unwrap());
+        assert_eq!(text, TARGETS.replace("tags = [\"shell\"]   # keep", "tags = [\"shell\", \"core\"]   # keep"));
+
+        let text = edited(TARGETS, |file| file.set_tags("Bash", &[]).unwrap());
+        assert!(!text.contains("tags"));
+        assert!(text.contains("path = \"~/.bashrc\"\n"));
+    }
+
+    #[test]
+    fn empty_inline_config_becomes_tables() {
+        let text = edited("# none yet\nconfig = []\n", |file| file.add("Bash", "~/.bashrc").unwrap());
+        assert_eq!(text, "# none yet\n[[config]]\nname = \"Bash\"\npath = \"~/.bashrc\"\n");
+    }
+
+    #[test]
+    fn save_replaces_the_file_and_keeps_its_mode() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config_targets.toml");
+        fs::write(&path, TARGETS).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+
+        let mut file = TargetsFile::open(&path).unwrap();
+        file.add("Git", "~/.gitconfig").unwrap();
+        file.save().unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
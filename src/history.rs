@@ -0,0 +1,93 @@
+// history.rs
+
+use crate::backup::BACKUPS_DIR;
+use crate::console::say;
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// The operation log, kept next to the backups it refers to
+const HISTORY_FILE: &str = "history.toml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    Export,
+    Restore,
+    Revert,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Operation::Export => "export",
+            Operation::Restore => "restore",
+            Operation::Revert => "revert",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// One export, restore or revert that ran, or tried to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub time: String,
+    pub operation: Operation,
+    /// The archive or backup it worked on
+    pub subject: String,
+    pub ok: bool,
+    /// What it did, or why it failed
+    pub detail: String,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Log {
+    #[serde(default)]
+    entry: Vec<Entry>,
+}
+
+pub fn history_path() -> PathBuf {
+    Path::new(BACKUPS_DIR).join(HISTORY_FILE)
+}
+
+/// Add an operation to the log; `result` is its summary or its error
+pub fn record(operation: Operation, subject: &str, result: &Result<String, String>) {
+    let entry = Entry {
+        time: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        operation,
+        subject: subject.to_string(),
+        ok: result.is_ok(),
+        detail: match result {
+            Ok(summary) => summary.clone(),
+            Err(e) => e.trim_start_matches("❌ ").to_string(),
+        },
+    };
+    // A log that cannot be written is not worth failing the operation over
+    if let Err(e) = append(&entry) {
+        say!("⚠️  Could not update {}: {}", history_path().display(), e);
+    }
+}
+
+/// Entries are appended as `[[entry]]` tables, so the file is never rewritten
+fn append(entry: &Entry) -> io::Result<()> {
+    fs::create_dir_all(BACKUPS_DIR)?;
+    let text = toml::to_string(&Log { entry: vec![entry.clone()] }).map_err(io::Error::other)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(history_path())?;
+    writeln!(file, "{}", text)
+}
+
+/// Every recorded operation, newest first
+pub fn load() -> Result<Vec<Entry>, String> {
+    let path = history_path();
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(format!("❌ Could not read {}: {}", path.display(), e)),
+    };
+    let log: Log = toml::from_str(&content).map_err(|e| format!("❌ Invalid history file '{}': {}", path.display(), e))?;
+    Ok(log.entry.into_iter().rev().collect())
+}
@@ -0,0 +1,137 @@
+// host.rs
+
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::Command;
+
+/// Hostname of this machine, or "unknown" if it cannot be determined
+pub fn hostname() -> String {
+    let from_file = ["/proc/sys/kernel/hostname", "/etc/hostname"]
+        .iter()
+        .find_map(|p| fs::read_to_string(p).ok());
+
+    let from_command = || {
+        Command::new("hostname")
+            .output()
+            .ok()
+            .and_then(|out| String::from_utf8(out.stdout).ok())
+    };
+
+    from_file
+        .or_else(from_command)
+        .map(|h| h.trim().to_string())
+        .filter(|h| !h.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// `ID` and `ID_LIKE` from `/etc/os-release`, e.g. `["ubuntu", "debian"]`
+pub fn distro_ids() -> Vec<String> {
+    let content = fs::read_to_string("/etc/os-release")
+        .or_else(|_| fs::read_to_string("/usr/lib/os-release"))
+        .unwrap_or_default();
+
+    let value = |key: &str| {
+        content
+            .lines()
+            .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+            .map(|v| v.trim().trim_matches('"').trim_matches('\'').to_string())
+    };
+
+    let mut ids: Vec<String> = value("ID").into_iter().collect();
+    if let Some(like) = value("ID_LIKE") {
+        ids.extend(like.split_whitespace().map(str::to_string));
+    }
+    ids
+}
+
+/// Whether `name` is an executable on `$PATH` (or, if it contains a `/`, at that path)
+pub fn on_path(name: &str) -> bool {
+    let executable = |path: &Path| {
+        fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+    };
+    if name.contains('/') {
+        return executable(Path::new(name));
+    }
+    env::var_os("PATH")
+        .map(|path| env::split_paths(&path).any(|dir| executable(&dir.join(name))))
+        .unwrap_or(false)
+}
+
+/// Whether a graphical session is running (X11 or Wayland)
+pub fn has_desktop_session() -> bool {
+    let set = |var: &str| env::var_os(var).is_some_and(|v| !v.is_empty());
+    set("WAYLAND_DISPLAY")
+        || set("DISPLAY")
+        || env::var("XDG_SESSION_TYPE").is_ok_and(|t| t == "x11" || t == "wayland")
+}
+
+/// `when` conditions on a target; every condition given must hold for the target to be used
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct When {
+    /// Glob matched against the hostname, e.g. "work-*"
+    pub hostname: Option<String>,
+    /// Accepted `/etc/os-release` IDs; `ID_LIKE` counts too, so "debian" matches Ubuntu
+    #[serde(default)]
+    pub distro: Vec<String>,
+    /// Executables that must be on `$PATH`
+    #[serde(default)]
+    pub command: Vec<String>,
+    /// `VAR` must be set and non-empty, `VAR=value` must equal `value`
+    #[serde(default)]
+    pub env: Vec<String>,
+    /// Require (true) or rule out (false) a graphical session
+    pub desktop: Option<bool>,
+}
+
+impl When {
+    /// `Err` carries why the target does not apply on this machine
+    pub fn check(&self) -> Result<(), String> {
+        if let Some(pattern) = &self.hostname {
+            let host = hostname();
+            let pattern = glob::Pattern::new(pattern)
+                .map_err(|e| format!("invalid hostname pattern '{}': {}", pattern, e))?;
+            if !pattern.matches(&host) {
+                return Err(format!("hostname '{}' does not match '{}'", host, pattern));
+            }
+        }
+
+        if !self.distro.is_empty() {
+            let ids = distro_ids();
+            if !ids.iter().any(|id| self.distro.contains(id)) {
+                return Err(format!(
+                    "distro '{}' is not {}",
+                    ids.first().map(String::as_str).unwrap_or("unknown"),
+                    self.distro.join(" or ")
+                ));
+            }
+        }
+
+        if let Some(missing) = self.command.iter().find(|cmd| !on_path(cmd)) {
+            return Err(format!("'{}' is not on PATH", missing));
+        }
+
+        for spec in &self.env {
+            match spec.split_once('=') {
+                Some((var, wanted)) => {
+                    let actual = env::var(var).unwrap_or_default();
+                    if actual != wanted {
+                        return Err(format!("${} is not '{}'", var, wanted));
+                    }
+                }
+                None if env::var_os(spec).is_none_or(|v| v.is_empty()) => {
+                    return Err(format!("${} is not set", spec));
+                }
+                None => {}
+            }
+        }
+
+        match self.desktop {
+            Some(true) if !has_desktop_session() => Err("no desktop session".to_string()),
+            Some(false) if has_desktop_session() => Err("a desktop session is running".to_string()),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,256 @@
+// inspect.rs
+
+use crate::backup;
+use crate::config::{ConfigItem, ConfigManifest};
+use crate::paths;
+
+use flate2::read::GzDecoder;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tar::{Archive, EntryType};
+
+/// One tar entry, as listed by `ls`
+struct Listed {
+    path: PathBuf,
+    mode: String,
+    size: u64,
+    link: Option<PathBuf>,
+}
+
+fn open_archive(archive: &Path) -> io::Result<Archive<GzDecoder<File>>> {
+    Ok(Archive::new(GzDecoder::new(File::open(archive)?)))
+}
+
+/// Manifest written next to an archive by `create_archive`
+pub fn sibling_manifest(archive: &Path) -> PathBuf {
+    let name = archive.file_name().unwrap_or_default().to_string_lossy();
+    let stem = name.strip_suffix(".tar.gz").unwrap_or(&name);
+    archive.with_file_name(format!("{stem}.manifest.toml"))
+}
+
+/// `ls -l` style mode column, e.g. `drwxr-xr-x`
+fn mode_string(kind: EntryType, mode: u32) -> String {
+    let type_char = match kind {
+        EntryType::Directory => 'd',
+        EntryType::Symlink => 'l',
+        EntryType::Link => 'h',
+        _ => '-',
+    };
+    let mut out = String::from(type_char);
+    for shift in [6, 3, 0] {
+        let bits = (mode >> shift) & 0o7;
+        out.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        out.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        out.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+    }
+    out
+}
+
+/// Write the archive as a tree grouped by manifest item
+pub fn list(archive: &Path, manifest: Option<&Path>, out: &mut impl Write) -> io::Result<()> {
+    let manifest_path = manifest.map(Path::to_path_buf).unwrap_or_else(|| sibling_manifest(archive));
+    let items: Vec<ConfigItem> = match fs::read_to_string(&manifest_path) {
+        Ok(content) => toml::from_str::<ConfigManifest>(&content)
+            .map_err(|e| io::Error::other(format!("invalid manifest {}: {}", manifest_path.display(), e)))?
+            .items,
+        Err(_) if manifest.is_none() => vec![],
+        Err(e) => return Err(io::Error::new(e.kind(), format!("{}: {}", manifest_path.display(), e))),
+    };
+    let roots: Vec<PathBuf> = items.iter().map(|i| paths::archive_rel(&i.path)).collect();
+
+    // One bucket per manifest item, plus a trailing one for entries no item claims
+    let mut groups: Vec<Vec<Listed>> = (0..=items.len()).map(|_| vec![]).collect();
+
+    for entry in open_archive(archive)?.entries()? {
+        let entry = entry?;
+        let header = entry.header();
+        let path = paths::normalize(&entry.path()?);
+        if path.as_os_str().is_empty() {
+            continue;
+        }
+
+        let listed = Listed {
+            mode: mode_string(header.entry_type(), header.mode().unwrap_or(0)),
+            size: header.size().unwrap_or(0),
+            link: entry.link_name()?.map(|l| l.into_owned()),
+            path,
+        };
+        let group = roots
+            .iter()
+            .position(|root| listed.path.starts_with(root))
+            .unwrap_or(items.len());
+        groups[group].push(listed);
+    }
+
+    writeln!(out, "📦 Archive: {}", archive.display())?;
+    writeln!(out, "───────────────────────────────────────────────")?;
+
+    let mut total_files = 0;
+    let mut total_size = 0;
+
+    for (idx, entries) in groups.iter().enumerate() {
+        if entries.is_empty() && idx == items.len() {
+            continue;
+        }
+        match items.get(idx) {
+            Some(item) => writeln!(out, "\n🗂️  {} ({})", item.name, item.path)?,
+            None => writeln!(out, "\n❔ Not in manifest")?,
+        }
+        if entries.is_empty() {
+            writeln!(out, "   (missing from archive)")?;
+        }
+
+        let root = roots.get(idx);
+        for listed in entries {
+            // Indent by depth below the item's own path
+            let depth = root
+                .and_then(|r| listed.path.strip_prefix(r).ok())
+                .map(|rel| rel.components().count())
+                .unwrap_or(0);
+            let name = if depth == 0 {
+                listed.path.display().to_string()
+            } else {
+                listed.path.file_name().unwrap_or_default().to_string_lossy().to_string()
+            };
+            let suffix = match (&listed.link, listed.mode.starts_with('d')) {
+                (Some(target), _) => format!(" -> {}", target.display()),
+                (None, true) => "/".to_string(),
+                (None, false) => String::new(),
+            };
+
+            writeln!(
+                out,
+                "   {} {:>10}  {}{}{}",
+                listed.mode,
+                if listed.mode.starts_with('-') { backup::format_size(listed.size) } else { String::new() },
+                "  ".repeat(depth),
+                name,
+                suffix
+            )?;
+
+            if listed.mode.starts_with('-') {
+                total_files += 1;
+                total_size += listed.size;
+            }
+        }
+    }
+
+    writeln!(out, "\n  {} file(s), {}", total_files, backup::format_size(total_size))?;
+    Ok(())
+}
+
+/// Stream one file from the archive to `out`.
+///
+/// `path` is either as stored in the archive (`.bashrc`) or as written in the manifest (`~/.bashrc`, `/etc/hosts`).
+pub fn cat(archive: &Path, path: &str, out: &mut impl Write) -> io::Result<()> {
+    let wanted = if path.starts_with('~') || path.starts_with('/') || path.starts_with('$') {
+        paths::archive_rel(path)
+    } else {
+        paths::normalize(Path::new(path))
+    };
+
+    for entry in open_archive(archive)?.entries()? {
+        let mut entry = entry?;
+        if paths::normalize(&entry.path()?) != wanted {
+            continue;
+        }
+        return match entry.header().entry_type() {
+            EntryType::Directory => Err(io::Error::other(format!("'{}' is a directory", path))),
+            EntryType::Regular | EntryType::Continuous => {
+                io::copy(&mut entry, out)?;
+                Ok(())
+            }
+            _ => Err(io::Error::other(format!("'{}' is not a regular file", path))),
+        };
+    }
+
+    Err(io::Error::new(io::ErrorKind::NotFound, format!("'{}' not found in archive", path)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use tar::{Builder, Header};
+
+    /// An archive with one home directory item and one system file, plus its manifest
+    fn fixture(dir: &Path) -> PathBuf {
+        let archive = dir.join("restitch-archive.tar.gz");
+        let mut tar = Builder::new(GzEncoder::new(File::create(&archive).unwrap(), Compression::default()));
+        let mut add = |path: &str, kind: EntryType, mode: u32, data: &[u8]| {
+            let mut header = Header::new_gnu();
+            header.set_entry_type(kind);
+            header.set_mode(mode);
+            header.set_size(data.len() as u64);
+            tar.append_data(&mut header, path, data).unwrap();
+        };
+        add(".config/app", EntryType::Directory, 0o755, b"");
+        add(".config/app/app.toml", EntryType::Regular, 0o644, b"theme = \"dark\"\n");
+        add("__root__/etc/hosts", EntryType::Regular, 0o644, b"127.0.0.1 localhost\n");
+        tar.into_inner().unwrap().finish().unwrap();
+
+        let manifest = "[[items]]\nname = \"App\"\npath = \"~/.config/app\"\nselected = true\n\n\
+                        [[items]]\nname = \"Hosts\"\npath = \"/etc/hosts\"\nselected = true\n";
+        fs::write(sibling_manifest(&archive), manifest).unwrap();
+        archive
+    }
+
+    fn cat_to_string(archive: &Path, path: &str) -> io::Result<String> {
+        let mut out = vec![];
+        cat(archive, path, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn ls_groups_entries_by_item() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = fixture(dir.path());
+        let mut out = vec![];
+        list(&archive, None, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let (first, rest) = text.split_once('\n').unwrap();
+        assert_eq!(first, format!("📦 Archive: {}", archive.display()));
+        let expected = "\
+───────────────────────────────────────────────
+
+🗂️  App (~/.config/app)
+   drwxr-xr-x             .config/app/
+   -rw-r--r--       15 B    app.toml
+
+🗂️  Hosts (/etc/hosts)
+   -rw-r--r--       20 B  __root__/etc/hosts
+
+  2 file(s), 35 B
+";
+        assert_eq!(rest, expected);
+    }
+
+    #[test]
+    fn cat_prints_a_file_by_archive_or_manifest_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = fixture(dir.path());
+        assert_eq!(cat_to_string(&archive, ".config/app/app.toml").unwrap(), "theme = \"dark\"\n");
+        assert_eq!(cat_to_string(&archive, "~/.config/app/app.toml").unwrap(), "theme = \"dark\"\n");
+    }
+
+    #[test]
+    fn cat_of_a_system_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = fixture(dir.path());
+        assert_eq!(cat_to_string(&archive, "/etc/hosts").unwrap(), "127.0.0.1 localhost\n");
+        assert_eq!(cat_to_string(&archive, "__root__/etc/hosts").unwrap(), "127.0.0.1 localhost\n");
+    }
+
+    #[test]
+    fn cat_of_a_missing_entry_or_directory_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = fixture(dir.path());
+        let missing = cat_to_string(&archive, "~/.bashrc").unwrap_err();
+        assert_eq!(missing.kind(), io::ErrorKind::NotFound);
+        assert_eq!(missing.to_string(), "'~/.bashrc' not found in archive");
+        let directory = cat_to_string(&archive, "~/.config/app").unwrap_err();
+        assert_eq!(directory.to_string(), "'~/.config/app' is a directory");
+    }
+}
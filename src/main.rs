@@ -4,10 +4,29 @@ mod packager;
 mod config;
 mod restore;
 mod revert;
+mod merge;
+mod backup;
+mod host;
+mod prune;
+mod paths;
+mod privilege;
+mod inspect;
+mod safety;
+mod signing;
+mod template;
+mod check;
+mod editor;
+mod ui;
+mod details;
+mod console;
+mod history;
+mod dashboard;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use config::ConflictPolicy;
 use std::fs;
 use std::io;
+use std::path::PathBuf;
 use std::process;
 
 /// Restitch CLI – Export, Restore, or Revert Linux Configs
@@ -22,10 +41,47 @@ struct Args {
     #[arg(long)]
     revert: bool,
 
-    /// Simulate restore without writing files
+    /// Simulate restore or revert without writing files
     #[arg(long)]
     dry_run: bool,
 
+    /// Conflict policy for restore: POLICY for every item or NAME=POLICY for one
+    /// (overwrite, skip-if-exists, keep-both, newer-wins, merge)
+    #[arg(long, value_name = "[NAME=]POLICY")]
+    policy: Vec<String>,
+
+    /// Restore or revert into an alternate root (chroot, container rootfs, mounted image)
+    #[arg(long, value_name = "DIR")]
+    target_root: Option<PathBuf>,
+
+    /// Home directory to map home-relative paths into (inside --target-root, if given)
+    #[arg(long, value_name = "DIR")]
+    target_home: Option<PathBuf>,
+
+    /// Never use sudo/pkexec; system-level items that need root are skipped
+    #[arg(long)]
+    no_escalate: bool,
+
+    /// Restore archives that are unsigned or not signed by a trusted key
+    #[arg(long)]
+    allow_unsigned: bool,
+
+    /// Allow restoring system-level items under DIR (repeatable)
+    #[arg(long, value_name = "DIR")]
+    allow_root: Vec<String>,
+
+    /// Pick the entries of a [profile.NAME] from the targets file
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Pick entries carrying TAG (repeatable)
+    #[arg(long, value_name = "TAG")]
+    tag: Vec<String>,
+
+    /// Template variable for restore, overriding the host vars file (repeatable)
+    #[arg(long = "var", value_name = "NAME=VALUE")]
+    vars: Vec<String>,
+
     /// Optional path to archive (.tar.gz)
     #[arg()]
     archive: Option<String>,
@@ -37,6 +93,125 @@ struct Args {
     /// Optional path to config_targets.toml
     #[arg(long, default_value = "config_targets.toml")]
     config_path: String,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Remove old backups according to the [retention] policy
+    Prune {
+        /// Show what would be removed without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Keep the N most recent backups
+        #[arg(long, value_name = "N")]
+        keep_last: Option<usize>,
+
+        /// Keep the newest backup of each of the last N days
+        #[arg(long, value_name = "N")]
+        keep_daily: Option<usize>,
+
+        /// Keep the newest backup of each of the last N weeks
+        #[arg(long, value_name = "N")]
+        keep_weekly: Option<usize>,
+
+        /// Cap the combined size of all backups (e.g. 500M, 2G)
+        #[arg(long, value_name = "SIZE")]
+        max_size: Option<String>,
+    },
+
+    /// Validate a targets file and the files it includes
+    Check {
+        /// Targets file to check (default: --config-path)
+        file: Option<PathBuf>,
+    },
+
+    /// List known apps whose configs exist on this machine
+    Detect {
+        /// Also list apps that were not found
+        #[arg(long)]
+        all: bool,
+
+        /// Write a starter targets file for everything found
+        #[arg(long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Overwrite FILE if it already exists
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Create a key pair for signing exported archives
+    Keygen {
+        /// Replace an existing signing key
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Trust archives signed by a public key (a .pub file or hex string)
+    Trust {
+        /// Public key file or hex-encoded key
+        key: String,
+
+        /// Name to store the key under (defaults to the file name)
+        #[arg(long)]
+        name: Option<String>,
+    },
+
+    /// List an archive's contents, grouped by manifest item
+    Ls {
+        /// Path to archive (.tar.gz)
+        archive: PathBuf,
+
+        /// Manifest to group by (defaults to the one next to the archive)
+        #[arg(long, value_name = "FILE")]
+        manifest: Option<PathBuf>,
+    },
+
+    /// Print one file from an archive to stdout
+    Cat {
+        /// Path to archive (.tar.gz)
+        archive: PathBuf,
+
+        /// File inside the archive, e.g. .bashrc or ~/.config/app/a.conf
+        path: String,
+    },
+}
+
+/// Retention settings from the targets file; a missing file means no policy
+fn load_retention(config_path: &str) -> prune::RetentionPolicy {
+    if !std::path::Path::new(config_path).exists() {
+        return Default::default();
+    }
+    detectors::load_retention(config_path).unwrap_or_else(|msg| {
+        eprintln!("{msg}");
+        process::exit(1);
+    })
+}
+
+/// `[restore]` limits from the targets file; a missing file means the defaults
+fn load_limits(config_path: &str) -> safety::RestoreLimits {
+    if !std::path::Path::new(config_path).exists() {
+        return Default::default();
+    }
+    detectors::load_restore_limits(config_path).unwrap_or_else(|msg| {
+        eprintln!("{msg}");
+        process::exit(1);
+    })
+}
+
+/// `--profile` and `--tag` as a selection; an unknown profile is fatal
+fn load_selection(config_path: &str, profile: Option<&str>, tags: &[String]) -> config::Selection {
+    let profile = profile.map(|name| {
+        detectors::load_profile(config_path, name).unwrap_or_else(|msg| {
+            eprintln!("{msg}");
+            process::exit(1);
+        })
+    });
+    config::Selection { profile, tags: tags.to_vec() }
 }
 
 fn main() {
@@ -48,15 +223,212 @@ fn main() {
 
     let args = Args::parse();
 
-    // 🚫 Invalid usage: dry-run without --restore
-    if args.dry_run && !args.restore {
-        eprintln!("❌ '--dry-run' can only be used with '--restore'");
+    // 🧹 Prune backups
+    if let Some(Command::Prune { dry_run, keep_last, keep_daily, keep_weekly, max_size }) = &args.command {
+        let mut policy = load_retention(&args.config_path);
+        policy.keep_last = keep_last.or(policy.keep_last);
+        policy.keep_daily = keep_daily.or(policy.keep_daily);
+        policy.keep_weekly = keep_weekly.or(policy.keep_weekly);
+        policy.max_total_size = max_size.clone().or(policy.max_total_size);
+
+        if let Err(e) = prune::prune(&policy, *dry_run, true) {
+            eprintln!("❌ Prune failed: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    // 🩺 Validate the targets file
+    if let Some(Command::Check { file }) = &args.command {
+        let file = file.clone().unwrap_or_else(|| PathBuf::from(&args.config_path));
+        let report = check::check(&file);
+        report.print();
+        if report.diagnostics.is_empty() {
+            println!("✅ {}: no problems found", file.display());
+        } else {
+            println!("{}: {} error(s), {} warning(s)", file.display(), report.errors(), report.warnings());
+        }
+        if report.errors() > 0 {
+            process::exit(1);
+        }
+        return;
+    }
+
+    // 🔎 Detect installed apps
+    if let Some(Command::Detect { all, output, force }) = &args.command {
+        let detected = detectors::detect_installed();
+        let found = detected.iter().filter(|d| !d.found.is_empty()).count();
+
+        println!("🔎 Known configs on this machine:");
+        println!("───────────────────────────────────────────────");
+        for d in &detected {
+            if d.found.is_empty() {
+                if *all {
+                    println!("  ·  {:<18} (not found)", d.app.name);
+                }
+                continue;
+            }
+            println!("  ✅ {:<18} {}", d.app.name, d.found.join(", "));
+        }
+        println!("\n  {} of {} known apps found", found, detected.len());
+
+        if let Some(output) = output {
+            if output.exists() && !force {
+                eprintln!("❌ {} already exists (use --force to overwrite)", output.display());
+                process::exit(1);
+            }
+            if let Err(e) = fs::write(output, detectors::starter_targets(&detected)) {
+                eprintln!("❌ Could not write {}: {}", output.display(), e);
+                process::exit(1);
+            }
+            println!("📝 Starter targets written to {}", output.display());
+        }
+        return;
+    }
+
+    // 🔏 Signing keys
+    if let Some(Command::Keygen { force }) = &args.command {
+        match signing::generate_key(*force) {
+            Ok(key) => {
+                println!("🔑 Signing key created in {}/", signing::key_dir().display());
+                println!("   Public key: {}", hex::encode(key.as_bytes()));
+                println!("\n💡 Share signing.pub and have others run `restitch trust signing.pub`.");
+            }
+            Err(e) => {
+                eprintln!("❌ Could not create signing key: {}", e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(Command::Trust { key, name }) = &args.command {
+        match signing::trust_key(key, name.as_deref()) {
+            Ok(path) => println!("✅ Trusted key saved to {}", path.display()),
+            Err(e) => {
+                eprintln!("❌ Could not trust key: {}", e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // 🔍 Inspect an archive
+    if let Some(Command::Ls { archive, manifest }) = &args.command {
+        if let Err(e) = inspect::list(archive, manifest.as_deref(), &mut io::stdout().lock()) {
+            eprintln!("❌ Failed to list archive: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+    if let Some(Command::Cat { archive, path }) = &args.command {
+        if let Err(e) = inspect::cat(archive, path, &mut io::stdout().lock()) {
+            eprintln!("❌ Failed to read from archive: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    // 🚫 Invalid usage: dry-run without --restore or --revert
+    if args.dry_run && !args.restore && !args.revert {
+        eprintln!("❌ '--dry-run' can only be used with '--restore' or '--revert'");
+        process::exit(1);
+    }
+
+    // 🚫 Invalid usage: policy with --revert
+    if !args.policy.is_empty() && args.revert {
+        eprintln!("❌ '--policy' can only be used with '--restore' or the dashboard");
+        process::exit(1);
+    }
+
+    // 🚫 Invalid usage: target paths without --restore or --revert
+    if (args.target_root.is_some() || args.target_home.is_some()) && !args.restore && !args.revert {
+        eprintln!("❌ '--target-root' and '--target-home' can only be used with '--restore' or '--revert'");
+        process::exit(1);
+    }
+
+    // 🚫 Invalid usage: allow-unsigned with --revert
+    if args.allow_unsigned && args.revert {
+        eprintln!("❌ '--allow-unsigned' can only be used with '--restore' or the dashboard");
+        process::exit(1);
+    }
+
+    // 🚫 Invalid usage: allow-root with --revert
+    if !args.allow_root.is_empty() && args.revert {
+        eprintln!("❌ '--allow-root' can only be used with '--restore' or the dashboard");
+        process::exit(1);
+    }
+
+    // 🚫 Invalid usage: var with --revert
+    if !args.vars.is_empty() && args.revert {
+        eprintln!("❌ '--var' can only be used with '--restore' or the dashboard");
+        process::exit(1);
+    }
+
+    // 🚫 Invalid usage: profile or tag with --revert
+    if (args.profile.is_some() || !args.tag.is_empty()) && args.revert {
+        eprintln!("❌ '--profile' and '--tag' can only be used when exporting or with '--restore'");
+        process::exit(1);
+    }
+
+    // 🚫 Invalid usage: no-escalate without --restore or --revert
+    if args.no_escalate && !args.restore && !args.revert {
+        eprintln!("❌ '--no-escalate' can only be used with '--restore' or '--revert'");
         process::exit(1);
     }
 
+    let selection = load_selection(&args.config_path, args.profile.as_deref(), &args.tag);
+    let target = paths::TargetPaths::new(args.target_root.clone(), args.target_home.clone());
+
+    let mut restore_options = restore::RestoreOptions {
+        dry_run: args.dry_run,
+        target: target.clone(),
+        no_escalate: args.no_escalate,
+        allow_unsigned: args.allow_unsigned,
+        // The dashboard restores too, so only --revert goes without these
+        retention: if args.revert { Default::default() } else { load_retention(&args.config_path) },
+        limits: if args.revert { Default::default() } else { load_limits(&args.config_path) },
+        selection: selection.clone(),
+        ..Default::default()
+    };
+    restore_options.limits.allowed_roots.extend(args.allow_root.iter().cloned());
+    for spec in &args.vars {
+        match template::parse_var(spec) {
+            Ok((name, value)) => {
+                restore_options.vars.insert(name, value);
+            }
+            Err(e) => {
+                eprintln!("❌ Invalid '--var {}': {}", spec, e);
+                process::exit(1);
+            }
+        }
+    }
+    for spec in &args.policy {
+        let (name, policy) = match spec.rsplit_once('=') {
+            Some((name, policy)) => (Some(name), policy),
+            None => (None, spec.as_str()),
+        };
+        let policy = match policy.parse::<ConflictPolicy>() {
+            Ok(p) => p,
+            Err(e) => {
+                eprintln!("❌ Invalid '--policy {}': {}", spec, e);
+                process::exit(1);
+            }
+        };
+        match name {
+            Some(name) => {
+                restore_options.item_policies.insert(name.to_string(), policy);
+            }
+            None => restore_options.policy = Some(policy),
+        }
+    }
+
     // 🔁 Revert (always uses TUI selector)
     if args.revert {
-        if let Err(e) = revert::run_revert_ui() {
+        if let Err(e) = revert::run_revert_ui(
+            &target,
+            privilege::Escalation::detect(!args.no_escalate),
+            args.dry_run,
+        ) {
             eprintln!("❌ Revert UI error: {}", e);
             process::exit(1);
         }
@@ -71,16 +443,19 @@ fn main() {
             if let Err(e) = restore::run_restore_ui(
                 "outputs/restitch-archive.manifest.toml",
                 "outputs/restitch-archive.tar.gz",
-                args.dry_run,
+                &restore_options,
             ) {
-                eprintln!("❌ Restore UI error: {}", e);
+                println!("{}", e);
                 process::exit(1);
             }
         } else {
             // Check that manifest exists and is readable
             match fs::read_to_string(&manifest) {
                 Ok(_) => {
-                    restore::restore_configs(&archive, &manifest, args.dry_run);
+                    if let Err(e) = restore::restore_configs(&archive, &manifest, &restore_options) {
+                        println!("{}", e);
+                        process::exit(1);
+                    }
                 }
                 Err(err) if err.kind() == io::ErrorKind::NotFound => {
                     eprintln!("❌ Manifest file not found: '{}'", manifest);
@@ -93,20 +468,9 @@ fn main() {
             }
         }
     }
-    // 📦 Default mode: Package (TUI for selecting configs)
-    else {
-        match tui::run_ui_with_cleanup(&args.config_path) {
-            Ok(items) => {
-                if items.is_empty() {
-                    println!("⚠️ No config items selected. Nothing to export.");
-                } else {
-                    packager::create_archive(&items, "restitch-archive");
-                }
-            }
-            Err(e) => {
-                eprintln!("❌ UI error: {}", e);
-                process::exit(1);
-            }
-        }
+    // 🗂️ Default mode: the dashboard, with export, restore, revert and history tabs
+    else if let Err(e) = dashboard::run(&args.config_path, &selection, &restore_options) {
+        eprintln!("❌ UI error: {}", e);
+        process::exit(1);
     }
 }
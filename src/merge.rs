@@ -0,0 +1,550 @@
+// merge.rs
+
+use crate::paths;
+use crate::ui::{self, Line, SelectList, TerminalGuard};
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crossterm::event::KeyCode;
+use tar::Archive;
+use tui::{
+    layout::{Constraint, Direction, Layout},
+    widgets::{Block, Borders, Paragraph},
+    Terminal,
+};
+
+/// Files larger than this are never treated as mergeable text
+pub const MAX_TEXT_SIZE: u64 = 1024 * 1024;
+
+/// How a single conflict was settled in the resolver screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Ours,
+    Theirs,
+    Both,
+}
+
+/// One region of a three-way merge result
+#[derive(Debug, Clone)]
+pub enum MergeChunk {
+    Clean(Vec<String>),
+    Conflict {
+        ours: Vec<String>,
+        theirs: Vec<String>,
+        resolution: Option<Resolution>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct MergeResult {
+    pub chunks: Vec<MergeChunk>,
+}
+
+/// A planned merge of one live file against its archived copy
+#[derive(Debug, Clone)]
+pub struct FileMerge {
+    pub dest: PathBuf,
+    pub result: MergeResult,
+}
+
+impl MergeResult {
+    pub fn conflicts(&self) -> usize {
+        self.chunks
+            .iter()
+            .filter(|c| matches!(c, MergeChunk::Conflict { .. }))
+            .count()
+    }
+
+    pub fn unresolved(&self) -> usize {
+        self.chunks
+            .iter()
+            .filter(|c| matches!(c, MergeChunk::Conflict { resolution: None, .. }))
+            .count()
+    }
+
+    /// Render the merged text, writing conflict markers for anything unresolved
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for chunk in &self.chunks {
+            match chunk {
+                MergeChunk::Clean(lines) => push_lines(&mut out, lines),
+                MergeChunk::Conflict { ours, theirs, resolution } => match resolution {
+                    Some(Resolution::Ours) => push_lines(&mut out, ours),
+                    Some(Resolution::Theirs) => push_lines(&mut out, theirs),
+                    Some(Resolution::Both) => {
+                        push_lines(&mut out, ours);
+                        end_line(&mut out);
+                        push_lines(&mut out, theirs);
+                    }
+                    None => {
+                        out.push_str("<<<<<<< local\n");
+                        push_lines(&mut out, ours);
+                        end_line(&mut out);
+                        out.push_str("=======\n");
+                        push_lines(&mut out, theirs);
+                        end_line(&mut out);
+                        out.push_str(">>>>>>> archive\n");
+                    }
+                },
+            }
+        }
+        out
+    }
+}
+
+/// Append lines as they were, keeping a missing newline at the end of the file
+fn push_lines(out: &mut String, lines: &[String]) {
+    for line in lines {
+        out.push_str(line);
+    }
+}
+
+/// Finish an unterminated last line, so whatever follows starts on its own line
+fn end_line(out: &mut String) {
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// Largest table `lcs_matches` builds; a bigger changed region is left unmatched
+const MAX_LCS_CELLS: usize = 1 << 22;
+
+/// For every line of `base`, the index of the matching line in `other` (longest common subsequence)
+fn lcs_matches(base: &[&str], other: &[&str]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; base.len()];
+
+    // Lines shared at the start and end match directly, so the table only covers the changed middle
+    let prefix = base.iter().zip(other).take_while(|(x, y)| x == y).count();
+    let suffix = base[prefix..]
+        .iter()
+        .rev()
+        .zip(other[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    for (i, slot) in matches.iter_mut().enumerate().take(prefix) {
+        *slot = Some(i);
+    }
+    for k in 1..=suffix {
+        matches[base.len() - k] = Some(other.len() - k);
+    }
+
+    // A rewrite too large to diff stays unmatched, and surfaces as a conflict instead of exhausting memory
+    let (base, other) = (&base[prefix..base.len() - suffix], &other[prefix..other.len() - suffix]);
+    let (n, m) = (base.len(), other.len());
+    if (n + 1).saturating_mul(m + 1) > MAX_LCS_CELLS {
+        return matches;
+    }
+    let width = m + 1;
+    let mut dp = vec![0u32; (n + 1) * width];
+
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            dp[i * width + j] = if base[i] == other[j] {
+                dp[(i + 1) * width + j + 1] + 1
+            } else {
+                dp[(i + 1) * width + j].max(dp[i * width + j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if base[i] == other[j] {
+            matches[prefix + i] = Some(prefix + j);
+            i += 1;
+            j += 1;
+        } else if dp[(i + 1) * width + j] >= dp[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    matches
+}
+
+fn to_owned(lines: &[&str]) -> Vec<String> {
+    lines.iter().map(|l| l.to_string()).collect()
+}
+
+/// Line-based three-way merge of `ours` and `theirs` against their common ancestor `base`
+pub fn merge3(base: &str, ours: &str, theirs: &str) -> MergeResult {
+    let o: Vec<&str> = base.split_inclusive('\n').collect();
+    let a: Vec<&str> = ours.split_inclusive('\n').collect();
+    let b: Vec<&str> = theirs.split_inclusive('\n').collect();
+
+    let match_a = lcs_matches(&o, &a);
+    let match_b = lcs_matches(&o, &b);
+
+    let mut chunks: Vec<MergeChunk> = vec![];
+    let mut stable: Vec<String> = vec![];
+    let (mut i, mut ia, mut ib) = (0, 0, 0);
+
+    loop {
+        // Lines unchanged on both sides
+        while i < o.len() && match_a[i] == Some(ia) && match_b[i] == Some(ib) {
+            stable.push(o[i].to_string());
+            i += 1;
+            ia += 1;
+            ib += 1;
+        }
+
+        // Next base line both sides still agree on
+        let mut j = i;
+        while j < o.len() && !(match_a[j].is_some() && match_b[j].is_some()) {
+            j += 1;
+        }
+        let (ja, jb) = if j < o.len() {
+            (match_a[j].unwrap(), match_b[j].unwrap())
+        } else {
+            (a.len(), b.len())
+        };
+
+        let (base_part, ours_part, theirs_part) = (&o[i..j], &a[ia..ja], &b[ib..jb]);
+        let resolved = if ours_part == base_part {
+            Some(theirs_part)
+        } else if theirs_part == base_part || ours_part == theirs_part {
+            Some(ours_part)
+        } else {
+            None
+        };
+
+        match resolved {
+            Some(lines) => stable.extend(to_owned(lines)),
+            None => {
+                if !stable.is_empty() {
+                    chunks.push(MergeChunk::Clean(std::mem::take(&mut stable)));
+                }
+                chunks.push(MergeChunk::Conflict {
+                    ours: to_owned(ours_part),
+                    theirs: to_owned(theirs_part),
+                    resolution: None,
+                });
+            }
+        }
+
+        if j >= o.len() {
+            break;
+        }
+        i = j;
+        ia = ja;
+        ib = jb;
+    }
+
+    if !stable.is_empty() {
+        chunks.push(MergeChunk::Clean(stable));
+    }
+
+    MergeResult { chunks }
+}
+
+fn as_text(bytes: Vec<u8>) -> Option<String> {
+    if bytes.contains(&0) {
+        return None;
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Read a tar entry (or any reader of `size` bytes) only if it looks like line-based text
+pub fn read_text_from(mut reader: impl Read, size: u64) -> Option<String> {
+    if size > MAX_TEXT_SIZE {
+        return None;
+    }
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes).ok()?;
+    as_text(bytes)
+}
+
+/// Read a file only if it looks like line-based text
+pub fn read_text(path: &Path) -> Option<String> {
+    let meta = fs::metadata(path).ok()?;
+    if !meta.is_file() || meta.len() > MAX_TEXT_SIZE {
+        return None;
+    }
+    as_text(fs::read(path).ok()?)
+}
+
+/// Where the previous export is kept once a newer archive replaces it
+pub fn base_archive_path(archive_path: &Path) -> PathBuf {
+    let name = archive_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let stem = name.strip_suffix(".tar.gz").unwrap_or(&name);
+    archive_path.with_file_name(format!("{stem}.prev.tar.gz"))
+}
+
+/// Load the text files of the previous export, keyed by their home-relative path
+pub fn load_base(archive_path: &Path) -> HashMap<PathBuf, String> {
+    let mut base = HashMap::new();
+    let Ok(file) = fs::File::open(archive_path) else {
+        return base;
+    };
+
+    let mut archive = Archive::new(flate2::read::GzDecoder::new(file));
+    let Ok(entries) = archive.entries() else {
+        return base;
+    };
+
+    for entry in entries.flatten() {
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let Ok(path) = entry.path().map(|p| paths::normalize(&p)) else {
+            continue;
+        };
+        let size = entry.size();
+        if let Some(text) = read_text_from(entry, size) {
+            base.insert(path, text);
+        }
+    }
+    base
+}
+
+/// Compute merges for every archived text file whose live copy drifted from the base.
+///
+/// `archived` holds the text files of the archive being restored, keyed like `base`.
+pub fn plan_merges(
+    archived: &HashMap<PathBuf, String>,
+    archive_rel: &Path,
+    dest: &Path,
+    base: &HashMap<PathBuf, String>,
+) -> Vec<FileMerge> {
+    let mut merges = vec![];
+
+    let mut keys: Vec<&PathBuf> = archived.keys().filter(|k| k.starts_with(archive_rel)).collect();
+    keys.sort();
+
+    for key in keys {
+        let Ok(sub) = key.strip_prefix(archive_rel) else {
+            continue;
+        };
+        let dest = if sub.as_os_str().is_empty() {
+            dest.to_path_buf()
+        } else {
+            dest.join(sub)
+        };
+
+        let (Some(ancestor), Some(theirs), Some(ours)) = (
+            base.get(key),
+            archived.get(key).cloned(),
+            read_text(&dest),
+        ) else {
+            continue;
+        };
+
+        // Nothing local to keep, a plain replace is already the right answer
+        if ours == theirs || &ours == ancestor {
+            continue;
+        }
+
+        let result = merge3(ancestor, &ours, &theirs);
+        if result.render() != theirs {
+            merges.push(FileMerge { dest, result });
+        }
+    }
+
+    merges
+}
+
+/// File and chunk index of every conflict
+fn conflict_positions(merges: &[FileMerge]) -> Vec<(usize, usize)> {
+    merges
+        .iter()
+        .enumerate()
+        .flat_map(|(fi, m)| {
+            m.result
+                .chunks
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| matches!(c, MergeChunk::Conflict { .. }))
+                .map(move |(ci, _)| (fi, ci))
+        })
+        .collect()
+}
+
+/// Interactive screen to settle conflicts one by one
+pub fn run_conflict_ui(merges: &mut [FileMerge]) -> io::Result<()> {
+    if conflict_positions(merges).is_empty() {
+        return Ok(());
+    }
+    let mut terminal = TerminalGuard::enter()?;
+    resolve_on(&mut terminal, merges)
+}
+
+/// The conflict screen on a terminal that is already set up, such as the dashboard's
+pub fn resolve_on<B: tui::backend::Backend>(terminal: &mut Terminal<B>, merges: &mut [FileMerge]) -> io::Result<()> {
+    let conflicts = conflict_positions(merges);
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+    ui_loop(terminal, merges, &conflicts)
+}
+
+fn ui_loop<B: tui::backend::Backend>(
+    terminal: &mut Terminal<B>,
+    merges: &mut [FileMerge],
+    conflicts: &[(usize, usize)],
+) -> io::Result<()> {
+    let mut list = SelectList::default();
+
+    loop {
+        let lines = conflicts
+            .iter()
+            .map(|&(fi, ci)| {
+                let label = match &merges[fi].result.chunks[ci] {
+                    MergeChunk::Conflict { resolution, .. } => match resolution {
+                        Some(Resolution::Ours) => "[local]   ",
+                        Some(Resolution::Theirs) => "[archive] ",
+                        Some(Resolution::Both) => "[both]    ",
+                        None => "[ ]       ",
+                    },
+                    MergeChunk::Clean(_) => "",
+                };
+                Line::new(label, format!("{} (#{})", merges[fi].dest.display(), ci + 1))
+            })
+            .collect();
+        list.set_lines(lines);
+        let resolved = conflicts
+            .iter()
+            .filter(|&&(fi, ci)| matches!(&merges[fi].result.chunks[ci], MergeChunk::Conflict { resolution: Some(_), .. }))
+            .count();
+
+        terminal.draw(|f| {
+            let size = f.size();
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Percentage(35), Constraint::Min(1), Constraint::Length(3)].as_ref())
+                .split(size);
+
+            list.render(f, chunks[0], "🌀 Restitch: Resolve Merge Conflicts");
+
+            let panes = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+                .split(chunks[1]);
+
+            if let Some(&(fi, ci)) = list.selected().and_then(|i| conflicts.get(i)) {
+                if let MergeChunk::Conflict { ours, theirs, .. } = &merges[fi].result.chunks[ci] {
+                    let local = Paragraph::new(ours.concat())
+                        .block(Block::default().title("Local").borders(Borders::ALL));
+                    let archive = Paragraph::new(theirs.concat())
+                        .block(Block::default().title("Archive").borders(Borders::ALL));
+                    f.render_widget(local, panes[0]);
+                    f.render_widget(archive, panes[1]);
+                }
+            }
+
+            ui::help_bar(
+                f,
+                chunks[2],
+                "↑↓: Navigate  /: Search  l: Keep local  a: Take archive  b: Both  u: Unset  enter: Apply  q: Leave markers",
+                &format!("✔ {} of {} conflict(s) resolved", resolved, conflicts.len()),
+            );
+        })?;
+
+        let Some(key) = ui::next_event()?.and_then(|event| list.handle(event)) else {
+            continue;
+        };
+        let choice = match key.code {
+            KeyCode::Char('l') => Some(Some(Resolution::Ours)),
+            KeyCode::Char('a') => Some(Some(Resolution::Theirs)),
+            KeyCode::Char('b') => Some(Some(Resolution::Both)),
+            KeyCode::Char('u') => Some(None),
+            _ => None,
+        };
+
+        match key.code {
+            KeyCode::Enter => return Ok(()),
+            KeyCode::Char('q') => {
+                for &(fi, ci) in conflicts {
+                    if let MergeChunk::Conflict { resolution, .. } = &mut merges[fi].result.chunks[ci] {
+                        *resolution = None;
+                    }
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        if let (Some(choice), Some(i)) = (choice, list.selected()) {
+            let (fi, ci) = conflicts[i];
+            if let MergeChunk::Conflict { resolution, .. } = &mut merges[fi].result.chunks[ci] {
+                *resolution = choice;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merged(base: &str, ours: &str, theirs: &str) -> (String, usize) {
+        let result = merge3(base, ours, theirs);
+        (result.render(), result.conflicts())
+    }
+
+    #[test]
+    fn edits_to_different_lines_merge_cleanly() {
+        let base = "a\nb\nc\nd\n";
+        let (text, conflicts) = merged(base, "A\nb\nc\nd\n", "a\nb\nc\nD\n");
+        assert_eq!(conflicts, 0);
+        assert_eq!(text, "A\nb\nc\nD\n");
+    }
+
+    #[test]
+    fn edits_to_the_same_line_conflict() {
+        let result = merge3("a\nb\nc\n", "a\nours\nc\n", "a\ntheirs\nc\n");
+        assert_eq!(result.conflicts(), 1);
+        assert_eq!(result.render(), "a\n<<<<<<< local\nours\n=======\ntheirs\n>>>>>>> archive\nc\n");
+    }
+
+    #[test]
+    fn identical_edits_on_both_sides_merge_once() {
+        let (text, conflicts) = merged("a\nb\nc\n", "a\nB\nc\nd\n", "a\nB\nc\nd\n");
+        assert_eq!(conflicts, 0);
+        assert_eq!(text, "a\nB\nc\nd\n");
+    }
+
+    #[test]
+    fn empty_base() {
+        assert_eq!(merged("", "", "new\n"), ("new\n".to_string(), 0));
+        assert_eq!(merged("", "same\n", "same\n"), ("same\n".to_string(), 0));
+        assert_eq!(merged("", "ours\n", "theirs\n").1, 1);
+    }
+
+    #[test]
+    fn missing_trailing_newline_is_kept() {
+        assert_eq!(merged("a\nb\nc", "A\nb\nc", "a\nb\nC"), ("A\nb\nC".to_string(), 0));
+        assert_eq!(merged("a\nb\nc\n", "a\nb\nc", "A\nb\nc\n"), ("A\nb\nc".to_string(), 0));
+    }
+
+    #[test]
+    fn conflict_on_an_unterminated_last_line() {
+        let mut result = merge3("a\nb", "a\nours", "a\ntheirs");
+        assert_eq!(result.render(), "a\n<<<<<<< local\nours\n=======\ntheirs\n>>>>>>> archive\n");
+
+        let set = |result: &mut MergeResult, choice| {
+            if let MergeChunk::Conflict { resolution, .. } = &mut result.chunks[1] {
+                *resolution = Some(choice);
+            }
+        };
+        set(&mut result, Resolution::Ours);
+        assert_eq!(result.render(), "a\nours");
+        set(&mut result, Resolution::Both);
+        assert_eq!(result.render(), "a\nours\ntheirs");
+    }
+
+    #[test]
+    fn huge_rewrite_conflicts_instead_of_diffing() {
+        let lines = |tag: &str| (0..200_000).map(|i| format!("{tag}{i}\n")).collect::<String>();
+        let (base, ours, theirs) = (lines("b"), lines("o"), lines("t"));
+        let result = merge3(&format!("top\n{base}end\n"), &format!("top\n{ours}end\n"), &format!("top\n{theirs}end\n"));
+        assert_eq!(result.conflicts(), 1);
+        assert!(result.render().starts_with("top\n<<<<<<< local\n"));
+        assert!(result.render().ends_with(">>>>>>> archive\nend\n"));
+    }
+}
@@ -1,39 +1,123 @@
 // packager.rs
 
-use crate::config::{ConfigItem, ConfigManifest};
+use crate::config::{ConfigItem, ConfigManifest, Ownership};
+use crate::console::say;
+use crate::history::{self, Operation};
+use crate::merge;
+use crate::paths;
+use crate::signing;
+use crate::template::{self, Vars};
 
 use std::fs::{self, File};
 use std::io::{BufWriter, Write};
-use std::path::{Path, PathBuf};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
 
-use tar::Builder;
+use glob::Pattern;
+use tar::{Builder, Header};
 use flate2::write::GzEncoder;
 use flate2::Compression;
 
-/// Add a file or directory into the tarball, using home-relative paths
+/// Where exports are written
+pub const OUTPUT_DIR: &str = "outputs";
+
+/// Whether a path below a target matches one of its `exclude` patterns.
+///
+/// Patterns without a `/` match any single component, like in `.gitignore`.
+pub fn is_excluded(rel: &Path, exclude: &[Pattern]) -> bool {
+    exclude.iter().any(|pattern| {
+        if pattern.as_str().contains('/') {
+            rel.ancestors().any(|prefix| pattern.matches_path(prefix))
+        } else {
+            rel.components().any(|c| pattern.matches(&c.as_os_str().to_string_lossy()))
+        }
+    })
+}
+
+/// Add one file; for templates, text files are stored with this machine's values as placeholders
+fn append_file<T: Write>(
+    tar: &mut Builder<T>,
+    source: &Path,
+    name: &Path,
+    vars: Option<&Vars>,
+) -> std::io::Result<()> {
+    let templated = vars.and_then(|vars| merge::read_text(source).map(|text| template::templatize(&text, vars)));
+    match templated {
+        Some(text) => {
+            let mut header = Header::new_gnu();
+            header.set_metadata(&fs::metadata(source)?);
+            header.set_size(text.len() as u64);
+            tar.append_data(&mut header, name, text.as_bytes())
+        }
+        None => tar.append_path_with_name(source, name),
+    }
+}
+
+/// Add a file or directory into the tarball under its portable archive path
 fn add_path_to_tar<T: Write>(
     tar: &mut Builder<T>,
     source: &Path,
-    base_dir: &Path,
+    archive_rel: &Path,
+    exclude: &[Pattern],
+    vars: Option<&Vars>,
 ) -> std::io::Result<()> {
     if source.is_file() {
-        tar.append_path_with_name(source, source.strip_prefix(base_dir).unwrap())?;
+        append_file(tar, source, archive_rel, vars)?;
+    } else if source.is_dir() && exclude.is_empty() && vars.is_none() {
+        tar.append_dir_all(archive_rel, source)?;
     } else if source.is_dir() {
-        tar.append_dir_all(source.strip_prefix(base_dir).unwrap(), source)?;
+        let mut walker = walkdir::WalkDir::new(source).follow_links(true).sort_by_file_name().into_iter();
+        while let Some(entry) = walker.next() {
+            let entry = entry.map_err(std::io::Error::other)?;
+            let rel = entry.path().strip_prefix(source).unwrap_or(Path::new(""));
+            if is_excluded(rel, exclude) {
+                if entry.file_type().is_dir() {
+                    walker.skip_current_dir();
+                }
+                continue;
+            }
+
+            let name = archive_rel.join(rel);
+            if entry.file_type().is_dir() {
+                tar.append_dir(&name, entry.path())?;
+            } else {
+                append_file(tar, entry.path(), &name, vars)?;
+            }
+        }
     }
     Ok(())
 }
 
+/// Ownership of a system-level target, so restore can put it back as it was
+fn system_owner(path: &str) -> Option<Ownership> {
+    if !paths::is_system(path) {
+        return None;
+    }
+    let meta = fs::metadata(paths::expand(path)).ok()?;
+    Some(Ownership {
+        uid: meta.uid(),
+        gid: meta.gid(),
+        mode: meta.mode(),
+    })
+}
+
 /// Create a .tar.gz archive and a manifest.toml for selected config items
-pub fn create_archive(items: &[ConfigItem], archive_name: &str) {
-    let home = dirs::home_dir().expect("Could not get home directory");
+pub fn create_archive(items: &[ConfigItem], archive_name: &str) -> Result<(), String> {
+    let output_dir = Path::new(OUTPUT_DIR);
+    let archive_path = output_dir.join(format!("{archive_name}.tar.gz"));
+    let manifest_path = output_dir.join(format!("{archive_name}.manifest.toml"));
+    let result = write_archive(items, &archive_path, &manifest_path);
+    history::record(Operation::Export, &archive_path.display().to_string(), &result);
+    result.map(|_| ())
+}
 
+/// Package the items, returning a summary for the history log
+fn write_archive(items: &[ConfigItem], archive_path: &Path, manifest_path: &Path) -> Result<String, String> {
     let mut valid_paths = vec![];
     let mut invalid_paths = vec![];
 
     for item in items {
-        let full_path = shellexpand::tilde(&item.path).into_owned();
-        let path = PathBuf::from(full_path);
+        let path = paths::expand(&item.path);
 
         if path.exists() {
             valid_paths.push((item, path));
@@ -42,51 +126,115 @@ pub fn create_archive(items: &[ConfigItem], archive_name: &str) {
         }
     }
 
-    println!("\n🔍 Restitch: Validating selected config targets");
-    println!("──────────────────────────────────────────────");
-    println!("  ✅ Valid configs:   {}", valid_paths.len());
-    println!("  ❌ Invalid configs: {}", invalid_paths.len());
+    say!("\n🔍 Restitch: Validating selected config targets");
+    say!("──────────────────────────────────────────────");
+    say!("  ✅ Valid configs:   {}", valid_paths.len());
+    say!("  ❌ Invalid configs: {}", invalid_paths.len());
 
     if !invalid_paths.is_empty() {
-        println!("\n❌ Skipping packaging. The following entries are invalid:");
+        say!("\n❌ Skipping packaging. The following entries are invalid:");
         for item in &invalid_paths {
-            println!("   - {} ({})", item.name, item.path);
+            say!("   - {} ({})", item.name, item.path);
         }
-        println!("\n💡 Fix or deselect these entries before proceeding.");
-        return;
+        say!("\n💡 Fix or deselect these entries before proceeding.");
+        return Err(format!("❌ {} invalid target(s), nothing was packaged", invalid_paths.len()));
     }
 
-    let output_dir = PathBuf::from("outputs");
-    fs::create_dir_all(&output_dir).expect("❌ Failed to create output directory");
+    // 🧩 Templates get this machine's values swapped for placeholders
+    let host_vars = if valid_paths.iter().any(|(item, _)| item.template) {
+        let vars = template::load_host_vars()?;
+        if vars.is_empty() {
+            say!("\n⚠️  No variables in {}, only hostname, user and home are templatized", template::vars_path().display());
+        }
+        template::machine_vars(vars)
+    } else {
+        Vars::new()
+    };
 
-    let archive_path = output_dir.join(format!("{archive_name}.tar.gz"));
-    let manifest_path = output_dir.join(format!("{archive_name}.manifest.toml"));
+    fs::create_dir_all(OUTPUT_DIR).map_err(|e| format!("❌ Failed to create output directory: {}", e))?;
+
+    // 🔀 Keep the previous export around as the common ancestor for `--merge`
+    if archive_path.exists() {
+        let base_path = merge::base_archive_path(archive_path);
+        fs::rename(archive_path, &base_path).map_err(|e| format!("❌ Failed to keep previous archive: {}", e))?;
+        say!("\n🗂️  Previous archive kept as merge base: {}", base_path.display());
+    }
 
     let archive_file = BufWriter::new(
-        File::create(&archive_path).expect("❌ Failed to create archive file"),
+        File::create(archive_path).map_err(|e| format!("❌ Failed to create archive file: {}", e))?,
     );
 
     let encoder = GzEncoder::new(archive_file, Compression::default());
     let mut tar = Builder::new(encoder);
 
-    println!("\n📦 Packaging:");
-    for (idx, (_item, path)) in valid_paths.iter().enumerate() {
+    say!("\n📦 Packaging:");
+    for (idx, (item, path)) in valid_paths.iter().enumerate() {
         let bullet = if idx == valid_paths.len() - 1 { "└─" } else { "├─" };
-        println!("  {} 📁 {}", bullet, path.display());
-        add_path_to_tar(&mut tar, path, &home).expect("❌ Failed to add to archive");
+        say!("  {} {} {}", bullet, if item.template { "🧩" } else { "📁" }, path.display());
+        let exclude: Vec<Pattern> = item.exclude.iter().filter_map(|p| Pattern::new(p).ok()).collect();
+        let vars = item.template.then_some(&host_vars);
+        add_path_to_tar(&mut tar, path, &paths::archive_rel(&item.path), &exclude, vars)
+            .map_err(|e| format!("❌ Failed to add {} to archive: {}", path.display(), e))?;
     }
 
-    tar.finish().expect("❌ Failed to finalize archive");
+    tar.into_inner()
+        .and_then(|encoder| encoder.finish())
+        .and_then(|mut file| file.flush())
+        .map_err(|e| format!("❌ Failed to finalize archive: {}", e))?;
 
     let manifest = ConfigManifest {
-        items: items.to_vec(),
+        items: items
+            .iter()
+            .map(|item| ConfigItem {
+                path: paths::portable(&item.path),
+                owner: system_owner(&item.path),
+                ..item.clone()
+            })
+            .collect(),
     };
 
     let toml_string = toml::to_string_pretty(&manifest).expect("Failed to serialize manifest");
-    fs::write(&manifest_path, toml_string).expect("Failed to write manifest.toml");
+    fs::write(manifest_path, toml_string).map_err(|e| format!("❌ Failed to write manifest.toml: {}", e))?;
+
+    // 🔏 Sign the export if this machine has a signing key; a stale signature must not linger
+    let signature_path = signing::signature_path(archive_path);
+    let _ = fs::remove_file(&signature_path);
+    let signed_by = signing::sign_archive(archive_path, manifest_path)
+        .map_err(|e| format!("❌ Failed to sign archive: {}", e))?;
+
+    say!("\n📁 Output Summary:");
+    say!("  📦 Archive:   {}", archive_path.display());
+    say!("  📝 Manifest:  {}", manifest_path.display());
+    match &signed_by {
+        Some(key) => say!("  🔏 Signature: {} (key {})", signature_path.display(), signing::fingerprint(key)),
+        None => say!("  ⚠️  Not signed: run `restitch keygen` to sign future exports"),
+    }
+    say!("\n✅ Restitch archive complete. Ready to use `--restore --dry-run`");
 
-    println!("\n📁 Output Summary:");
-    println!("  📦 Archive:   {}", archive_path.display());
-    println!("  📝 Manifest:  {}", manifest_path.display());
-    println!("\n✅ Restitch archive complete. Ready to use `--restore --dry-run`");
+    let signed = if signed_by.is_some() { "signed" } else { "unsigned" };
+    Ok(format!("{} target(s) packaged, {}", valid_paths.len(), signed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn templates_use_builtins_overridden_by_the_vars_file() {
+        let home = dirs::home_dir().unwrap().display().to_string();
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("gitconfig");
+        fs::write(&source, format!("name = octocat\nhome = {home}\n")).unwrap();
+        let vars = template::machine_vars(Vars::from([("user".to_string(), "octocat".to_string())]));
+
+        let mut tar = Builder::new(vec![]);
+        append_file(&mut tar, &source, Path::new("gitconfig"), Some(&vars)).unwrap();
+        let bytes = tar.into_inner().unwrap();
+
+        let mut archive = tar::Archive::new(bytes.as_slice());
+        let mut text = String::new();
+        archive.entries().unwrap().next().unwrap().unwrap().read_to_string(&mut text).unwrap();
+        assert_eq!(text, "name = {{ user }}\nhome = {{ home }}\n");
+    }
 }
@@ -0,0 +1,196 @@
+// paths.rs
+
+use std::path::{Component, Path, PathBuf};
+
+/// Archive prefix for system-level targets that live outside `$HOME`
+pub const ROOT_PREFIX: &str = "__root__";
+
+/// XDG base directories that may prefix a target, with their default home-relative location
+const XDG_DIRS: [(&str, &str); 4] = [
+    ("XDG_CONFIG_HOME", ".config"),
+    ("XDG_DATA_HOME", ".local/share"),
+    ("XDG_STATE_HOME", ".local/state"),
+    ("XDG_CACHE_HOME", ".cache"),
+];
+
+/// A manifest path split into its anchor and the remainder
+enum Anchored<'a> {
+    Home(PathBuf),
+    Xdg(&'static str, &'static str, &'a str),
+    Absolute(PathBuf),
+}
+
+fn anchor(path: &str) -> Anchored<'_> {
+    if path == "~" {
+        return Anchored::Home(PathBuf::new());
+    }
+    if let Some(rest) = path.strip_prefix("~/") {
+        return Anchored::Home(PathBuf::from(rest));
+    }
+
+    for (var, default) in XDG_DIRS {
+        for prefix in [format!("${var}"), format!("${{{var}}}")] {
+            if let Some(rest) = path.strip_prefix(&prefix) {
+                if rest.is_empty() || rest.starts_with('/') {
+                    return Anchored::Xdg(var, default, rest.trim_start_matches('/'));
+                }
+            }
+        }
+    }
+
+    match home_relative(Path::new(path)) {
+        Some(rel) => Anchored::Home(rel),
+        None => Anchored::Absolute(PathBuf::from(path)),
+    }
+}
+
+/// Portable form of a target path for the manifest: `~/…` or `$XDG_…/…` where possible.
+///
+/// Absolute paths under a home directory (including ones from older manifests) become `~/…`.
+pub fn portable(path: &str) -> String {
+    match anchor(path) {
+        Anchored::Home(rel) if rel.as_os_str().is_empty() => "~".to_string(),
+        Anchored::Home(rel) => format!("~/{}", rel.display()),
+        Anchored::Xdg(var, _, "") => format!("${var}"),
+        Anchored::Xdg(var, _, rest) => format!("${var}/{rest}"),
+        Anchored::Absolute(abs) => abs.to_string_lossy().to_string(),
+    }
+}
+
+/// Expand `~`, `$VAR`, `${VAR}` and `${VAR:-default}` in a path from the targets file.
+///
+/// The XDG base directories are left as written, so they stay portable anchors in the manifest.
+pub fn expand_vars(path: &str) -> Result<String, String> {
+    let lookup = |var: &str| -> Result<Option<String>, std::env::VarError> {
+        if XDG_DIRS.iter().any(|(xdg, _)| *xdg == var) {
+            return Ok(Some(format!("${var}")));
+        }
+        // Empty counts as unset, so `${VAR:-default}` falls back like in a shell
+        std::env::var(var).map(|value| Some(value).filter(|v| !v.is_empty()))
+    };
+    let home = || dirs::home_dir().map(|h| h.to_string_lossy().into_owned());
+    shellexpand::full_with_context(path, home, lookup)
+        .map(|expanded| expanded.into_owned())
+        .map_err(|e| format!("undefined variable ${} in '{}'", e.var_name, path))
+}
+
+/// Tar entry path without `./` components or a trailing slash
+pub fn normalize(path: &Path) -> PathBuf {
+    path.components().filter(|c| !matches!(c, Component::CurDir)).collect()
+}
+
+/// Machine-independent location of a target inside the archive
+pub fn archive_rel(path: &str) -> PathBuf {
+    match anchor(path) {
+        Anchored::Home(rel) => rel,
+        Anchored::Xdg(_, default, rest) => Path::new(default).join(rest),
+        Anchored::Absolute(abs) => Path::new(ROOT_PREFIX).join(strip_root(&abs)),
+    }
+}
+
+/// Whether a target is system-level, i.e. outside any home directory
+pub fn is_system(path: &str) -> bool {
+    matches!(anchor(path), Anchored::Absolute(_))
+}
+
+fn strip_root(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| !matches!(c, Component::RootDir | Component::Prefix(_)))
+        .collect()
+}
+
+/// Resolve a manifest path on this machine, honouring `$XDG_*` overrides
+pub fn expand(path: &str) -> PathBuf {
+    TargetPaths::default().dest_for(path)
+}
+
+/// Where restored files land: an optional alternate root and the home directory inside it
+#[derive(Debug, Clone)]
+pub struct TargetPaths {
+    /// Prefix for every destination, e.g. a chroot or mounted disk image
+    pub root: Option<PathBuf>,
+    /// Home directory that home-relative paths are rewritten into (inside `root`)
+    pub home: PathBuf,
+}
+
+impl Default for TargetPaths {
+    fn default() -> Self {
+        TargetPaths::new(None, None)
+    }
+}
+
+impl TargetPaths {
+    pub fn new(root: Option<PathBuf>, home: Option<PathBuf>) -> Self {
+        let home = home.unwrap_or_else(|| dirs::home_dir().expect("Could not get home directory"));
+        TargetPaths { root, home }
+    }
+
+    /// Rebase an absolute path under the alternate root, if any
+    pub fn rebase(&self, path: &Path) -> PathBuf {
+        match &self.root {
+            Some(root) => root.join(strip_root(path)),
+            None => path.to_path_buf(),
+        }
+    }
+
+    /// The target home as it appears on this machine
+    pub fn home_dir(&self) -> PathBuf {
+        self.rebase(&self.home)
+    }
+
+    /// Whether this is the current user's own home on the live system
+    fn is_local(&self) -> bool {
+        self.root.is_none() && dirs::home_dir().as_deref() == Some(self.home.as_path())
+    }
+
+    /// Destination for a path recorded in a manifest
+    pub fn dest_for(&self, path: &str) -> PathBuf {
+        match anchor(path) {
+            Anchored::Home(rel) => self.home_dir().join(rel),
+            Anchored::Xdg(var, default, rest) => {
+                // Another home's XDG overrides are unknown, so fall back to the spec defaults there
+                let env_dir = std::env::var_os(var)
+                    .filter(|v| !v.is_empty() && self.is_local())
+                    .map(PathBuf::from);
+                env_dir
+                    .unwrap_or_else(|| self.home_dir().join(default))
+                    .join(rest)
+            }
+            Anchored::Absolute(abs) => self.rebase(&abs),
+        }
+    }
+
+    /// Destination for a path as laid out inside an archive or backup
+    pub fn dest_for_archive_rel(&self, rel: &Path) -> PathBuf {
+        match rel.strip_prefix(ROOT_PREFIX) {
+            Ok(system) => self.rebase(&Path::new("/").join(system)),
+            Err(_) => self.home_dir().join(rel),
+        }
+    }
+}
+
+/// Strip whichever home directory an absolute path was recorded under.
+///
+/// Recognises the current user's home as well as `/home/<user>`, `/Users/<user>` and `/root`,
+/// so manifests exported by another user still map into the target home.
+pub fn home_relative(path: &Path) -> Option<PathBuf> {
+    if let Some(home) = dirs::home_dir() {
+        if let Ok(rel) = path.strip_prefix(&home) {
+            return Some(rel.to_path_buf());
+        }
+    }
+
+    let mut components = path.components();
+    if components.next() != Some(Component::RootDir) {
+        return None;
+    }
+
+    match components.next() {
+        Some(Component::Normal(dir)) if dir == "home" || dir == "Users" => {
+            components.next()?;
+            Some(components.as_path().to_path_buf())
+        }
+        Some(Component::Normal(dir)) if dir == "root" => Some(components.as_path().to_path_buf()),
+        _ => None,
+    }
+}
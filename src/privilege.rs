@@ -0,0 +1,179 @@
+// privilege.rs
+
+use crate::config::Ownership;
+use crate::host::on_path;
+use crate::restore::copy_dir_recursive;
+
+use std::ffi::{CString, OsStr};
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::process::Command;
+
+/// How destinations we cannot write ourselves get written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Escalation {
+    Root,
+    Sudo,
+    Pkexec,
+    Unavailable,
+}
+
+impl Escalation {
+    /// Pick the escalation method available on this machine
+    pub fn detect(allowed: bool) -> Self {
+        if is_root() {
+            Escalation::Root
+        } else if !allowed {
+            Escalation::Unavailable
+        } else if on_path("sudo") {
+            Escalation::Sudo
+        } else if on_path("pkexec") {
+            Escalation::Pkexec
+        } else {
+            Escalation::Unavailable
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Escalation::Root => "root",
+            Escalation::Sudo => "sudo",
+            Escalation::Pkexec => "pkexec",
+            Escalation::Unavailable => "unavailable",
+        }
+    }
+
+    /// Filesystem operations for a destination, or `None` if it cannot be written
+    pub fn ops_for(&self, path: &Path) -> Option<FsOps> {
+        if can_replace(path) {
+            return Some(FsOps::direct());
+        }
+        match self {
+            Escalation::Root => Some(FsOps::direct()),
+            Escalation::Sudo => Some(FsOps { helper: Some("sudo") }),
+            Escalation::Pkexec => Some(FsOps { helper: Some("pkexec") }),
+            Escalation::Unavailable => None,
+        }
+    }
+}
+
+pub fn is_root() -> bool {
+    // SAFETY: geteuid has no preconditions and cannot fail
+    unsafe { libc::geteuid() == 0 }
+}
+
+fn access_writable(path: &Path) -> bool {
+    let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // SAFETY: c_path is a valid NUL-terminated string for the duration of the call
+    unsafe { libc::access(c_path.as_ptr(), libc::W_OK) == 0 }
+}
+
+/// Whether we can move `path` aside and create it again without help
+pub fn can_replace(path: &Path) -> bool {
+    let mut ancestor = path.parent();
+    while let Some(dir) = ancestor {
+        if dir.exists() {
+            break;
+        }
+        ancestor = dir.parent();
+    }
+
+    let parent_ok = ancestor.map(access_writable).unwrap_or(false);
+    parent_ok && (path.symlink_metadata().is_err() || access_writable(path))
+}
+
+/// Filesystem operations, performed directly or through `sudo`/`pkexec`
+#[derive(Debug, Clone, Copy)]
+pub struct FsOps {
+    helper: Option<&'static str>,
+}
+
+impl FsOps {
+    pub fn direct() -> Self {
+        FsOps { helper: None }
+    }
+
+    pub fn is_escalated(&self) -> bool {
+        self.helper.is_some()
+    }
+
+    /// Run `program` through the helper; callers put `--` before paths so none is read as an option
+    fn run(&self, program: &str, args: &[&OsStr]) -> io::Result<()> {
+        let helper = self.helper.expect("only escalated ops shell out");
+        let status = Command::new(helper).arg(program).args(args).status()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!("{} {} failed ({})", helper, program, status)))
+        }
+    }
+
+    pub fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        match self.helper {
+            None => fs::rename(from, to),
+            Some(_) => self.run("mv", &["--".as_ref(), from.as_os_str(), to.as_os_str()]),
+        }
+    }
+
+    pub fn create_dir_all(&self, dir: &Path) -> io::Result<()> {
+        match self.helper {
+            None => fs::create_dir_all(dir),
+            Some(_) => self.run("mkdir", &["-p".as_ref(), "--".as_ref(), dir.as_os_str()]),
+        }
+    }
+
+    /// Remove a directory only if it is empty
+    pub fn remove_empty_dir(&self, path: &Path) -> io::Result<()> {
+        match self.helper {
+            None => fs::remove_dir(path),
+            Some(_) => self.run("rmdir", &["--".as_ref(), path.as_os_str()]),
+        }
+    }
+
+    /// Copy a file or directory tree
+    pub fn copy(&self, src: &Path, dst: &Path) -> io::Result<()> {
+        match self.helper {
+            None if src.is_dir() => copy_dir_recursive(src, dst),
+            None => fs::copy(src, dst).map(|_| ()),
+            Some(_) => self.run("cp", &["-r".as_ref(), "--".as_ref(), src.as_os_str(), dst.as_os_str()]),
+        }
+    }
+
+    pub fn write(&self, dst: &Path, contents: &str) -> io::Result<()> {
+        if self.helper.is_none() {
+            return fs::write(dst, contents);
+        }
+
+        // Stage the content in a fresh private file, then copy it over as the helper
+        let mut staged = tempfile::NamedTempFile::new()?;
+        staged.write_all(contents.as_bytes())?;
+        staged.flush()?;
+        let result = self.run("cp", &["--".as_ref(), staged.path().as_os_str(), dst.as_os_str()]);
+        staged.close()?;
+        result
+    }
+
+    /// Apply recorded ownership to a restored tree and permissions to its top
+    pub fn apply_ownership(&self, path: &Path, owner: &Ownership) -> io::Result<()> {
+        let mode = owner.mode & 0o7777;
+        match self.helper {
+            None => {
+                if is_root() {
+                    for entry in walkdir::WalkDir::new(path).into_iter().filter_map(Result::ok) {
+                        std::os::unix::fs::lchown(entry.path(), Some(owner.uid), Some(owner.gid))?;
+                    }
+                }
+                fs::set_permissions(path, std::os::unix::fs::PermissionsExt::from_mode(mode))
+            }
+            Some(_) => {
+                let ids = format!("{}:{}", owner.uid, owner.gid);
+                self.run("chown", &["-R".as_ref(), "--".as_ref(), ids.as_ref(), path.as_os_str()])?;
+                self.run("chmod", &["--".as_ref(), format!("{:o}", mode).as_ref(), path.as_os_str()])
+            }
+        }
+    }
+}
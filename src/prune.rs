@@ -0,0 +1,283 @@
+// prune.rs
+
+use crate::backup::{self, BACKUPS_DIR};
+use crate::console::{self, say};
+
+use chrono::{Datelike, NaiveDateTime};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+/// `[retention]` settings from the targets file; unset rules keep everything
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct RetentionPolicy {
+    pub keep_last: Option<usize>,
+    pub keep_daily: Option<usize>,
+    pub keep_weekly: Option<usize>,
+    /// Cap on the combined size of all backups, e.g. "500M" or "2GiB"
+    pub max_total_size: Option<String>,
+    /// Apply the policy at the end of every restore
+    #[serde(default)]
+    pub auto: bool,
+}
+
+impl RetentionPolicy {
+    fn has_keep_rules(&self) -> bool {
+        self.keep_last.is_some() || self.keep_daily.is_some() || self.keep_weekly.is_some()
+    }
+}
+
+/// Parse a size such as `1024`, `500M`, `2G` or `2GiB` into bytes
+pub fn parse_size(input: &str) -> Result<u64, String> {
+    let trimmed = input.trim();
+    let split = trimmed
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+
+    let value: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", input))?;
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return Err(format!("invalid size unit in '{}'", input)),
+    };
+    Ok((value * multiplier as f64) as u64)
+}
+
+/// One backup directory and whether the policy keeps it
+struct Candidate {
+    path: PathBuf,
+    name: String,
+    created: Option<NaiveDateTime>,
+    size: u64,
+    keep: Option<&'static str>,
+}
+
+impl Candidate {
+    fn new(path: PathBuf, size: u64) -> Self {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        Candidate {
+            created: name
+                .get(..19)
+                .and_then(|ts| NaiveDateTime::parse_from_str(ts, "%Y-%m-%d_%H-%M-%S").ok()),
+            size,
+            path,
+            name,
+            keep: None,
+        }
+    }
+}
+
+/// Decide which backups in `backups/` to keep, newest first
+fn evaluate(policy: &RetentionPolicy) -> Result<Vec<Candidate>, String> {
+    let candidates = match fs::read_dir(BACKUPS_DIR) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .filter(|e| e.path().is_dir())
+            .map(|e| Candidate::new(e.path(), backup::tree_stats(&e.path()).1))
+            .collect(),
+        Err(_) => vec![],
+    };
+    decide(policy, candidates)
+}
+
+/// Apply the policy to backups already found on disk
+fn decide(policy: &RetentionPolicy, mut candidates: Vec<Candidate>) -> Result<Vec<Candidate>, String> {
+    let max_size = policy.max_total_size.as_deref().map(parse_size).transpose()?;
+
+    // Newest first; directories that are not timestamps go last and are never pruned
+    candidates.sort_by(|a, b| b.created.is_some().cmp(&a.created.is_some()).then(b.created.cmp(&a.created)));
+
+    let mut days = HashSet::new();
+    let mut weeks = HashSet::new();
+
+    for (idx, c) in candidates.iter_mut().enumerate() {
+        let Some(created) = c.created else {
+            c.keep = Some("unrecognised name");
+            continue;
+        };
+
+        if !policy.has_keep_rules() {
+            c.keep = Some("no keep rules");
+        } else if policy.keep_last.is_some_and(|n| idx < n) {
+            c.keep = Some("keep-last");
+        }
+
+        if policy.keep_daily.is_some_and(|n| days.len() < n) && days.insert(created.date()) {
+            c.keep = c.keep.or(Some("keep-daily"));
+        }
+
+        let week = created.iso_week();
+        if policy.keep_weekly.is_some_and(|n| weeks.len() < n) && weeks.insert((week.year(), week.week())) {
+            c.keep = c.keep.or(Some("keep-weekly"));
+        }
+    }
+
+    // 📏 The size cap applies on top of the keep rules, but never drops the newest backup
+    if let Some(max_size) = max_size {
+        let mut total = 0;
+        for (idx, c) in candidates.iter_mut().enumerate() {
+            if c.keep.is_none() || c.created.is_none() {
+                continue;
+            }
+            total += c.size;
+            if idx > 0 && total > max_size {
+                c.keep = None;
+            }
+        }
+    }
+
+    Ok(candidates)
+}
+
+/// Apply a retention policy to `backups/`, asking first unless `confirm` is false
+pub fn prune(policy: &RetentionPolicy, dry_run: bool, confirm: bool) -> Result<(), String> {
+    let candidates = evaluate(policy)?;
+    if candidates.is_empty() {
+        say!("❌ No backups available.");
+        return Ok(());
+    }
+
+    say!("🧹 Prune Plan{}:", if dry_run { " (dry-run)" } else { "" });
+    say!("───────────────────────────────────────────────");
+    for c in &candidates {
+        match c.keep {
+            Some(reason) => say!("  ✅ KEEP:  {} ({}, {})", c.name, backup::format_size(c.size), reason),
+            None => say!("  🗑️  PRUNE: {} ({})", c.name, backup::format_size(c.size)),
+        }
+    }
+
+    let doomed: Vec<&Candidate> = candidates.iter().filter(|c| c.keep.is_none()).collect();
+    let freed: u64 = doomed.iter().map(|c| c.size).sum();
+    say!("\n  {} backup(s) to remove, freeing {}", doomed.len(), backup::format_size(freed));
+
+    if dry_run {
+        say!("\n🔎 Prune dry-run complete.");
+        return Ok(());
+    }
+    if doomed.is_empty() {
+        return Ok(());
+    }
+
+    if confirm {
+        say!();
+        if !console::confirm("Proceed with prune?") {
+            say!("\n❌ Prune cancelled.");
+            return Ok(());
+        }
+    }
+
+    for c in doomed {
+        match fs::remove_dir_all(&c.path) {
+            Ok(_) => say!("🗑️  Removed: {}", c.path.display()),
+            Err(e) => say!("⚠️  Could not remove {}: {}", c.path.display(), e),
+        }
+    }
+    say!("\n✅ Prune complete.");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Newest first: two on Fri 1 March, one on Wed 28 Feb (same ISO week), one a week earlier, one older still
+    const BACKUPS: [&str; 5] = [
+        "2024-03-01_10-00-00",
+        "2024-03-01_09-00-00",
+        "2024-02-28_12-00-00",
+        "2024-02-20_12-00-00",
+        "2024-02-10_12-00-00",
+    ];
+
+    /// Names of the backups `policy` keeps, each of them 100 bytes
+    fn kept(policy: RetentionPolicy) -> Vec<String> {
+        // Shuffled, so the order comes from the timestamps
+        let candidates = [3, 0, 4, 2, 1]
+            .iter()
+            .map(|&i| Candidate::new(PathBuf::from(BACKUPS_DIR).join(BACKUPS[i]), 100))
+            .collect();
+        decide(&policy, candidates)
+            .unwrap()
+            .into_iter()
+            .filter(|c| c.keep.is_some())
+            .map(|c| c.name)
+            .collect()
+    }
+
+    #[test]
+    fn no_rules_keep_everything() {
+        assert_eq!(kept(RetentionPolicy::default()), BACKUPS);
+    }
+
+    #[test]
+    fn keep_last_keeps_the_newest() {
+        let policy = RetentionPolicy { keep_last: Some(2), ..Default::default() };
+        assert_eq!(kept(policy), BACKUPS[..2]);
+    }
+
+    #[test]
+    fn keep_daily_keeps_the_newest_of_each_day() {
+        let policy = RetentionPolicy { keep_daily: Some(3), ..Default::default() };
+        assert_eq!(kept(policy), [BACKUPS[0], BACKUPS[2], BACKUPS[3]]);
+    }
+
+    #[test]
+    fn keep_weekly_keeps_the_newest_of_each_week() {
+        let policy = RetentionPolicy { keep_weekly: Some(2), ..Default::default() };
+        assert_eq!(kept(policy), [BACKUPS[0], BACKUPS[3]]);
+    }
+
+    #[test]
+    fn max_size_drops_the_oldest_past_the_cap() {
+        let policy = RetentionPolicy { max_total_size: Some("250".to_string()), ..Default::default() };
+        assert_eq!(kept(policy), BACKUPS[..2]);
+    }
+
+    #[test]
+    fn max_size_never_drops_the_newest() {
+        let policy = RetentionPolicy { max_total_size: Some("50".to_string()), ..Default::default() };
+        assert_eq!(kept(policy), BACKUPS[..1]);
+    }
+
+    #[test]
+    fn keep_rules_add_up() {
+        let policy = RetentionPolicy { keep_last: Some(2), keep_weekly: Some(3), ..Default::default() };
+        assert_eq!(kept(policy), [BACKUPS[0], BACKUPS[1], BACKUPS[3], BACKUPS[4]]);
+    }
+
+    #[test]
+    fn max_size_caps_what_keep_rules_keep() {
+        let policy = RetentionPolicy {
+            keep_daily: Some(3),
+            max_total_size: Some("250".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(kept(policy), [BACKUPS[0], BACKUPS[2]]);
+    }
+
+    #[test]
+    fn unrecognised_names_are_kept_last() {
+        let candidates = vec![
+            Candidate::new(PathBuf::from(BACKUPS_DIR).join("manual"), 100),
+            Candidate::new(PathBuf::from(BACKUPS_DIR).join(BACKUPS[4]), 100),
+            Candidate::new(PathBuf::from(BACKUPS_DIR).join(BACKUPS[0]), 100),
+        ];
+        let policy = RetentionPolicy { keep_last: Some(1), ..Default::default() };
+        let decided: Vec<_> = decide(&policy, candidates).unwrap().into_iter().map(|c| (c.name, c.keep)).collect();
+        assert_eq!(
+            decided,
+            [
+                (BACKUPS[0].to_string(), Some("keep-last")),
+                (BACKUPS[4].to_string(), None),
+                ("manual".to_string(), Some("unrecognised name")),
+            ]
+        );
+    }
+}
@@ -1,123 +1,633 @@
-use crate::config::{ConfigItem, ConfigManifest};
+use crate::backup::{Backup, BackupMeta};
+use crate::host;
+use crate::config::{ConfigItem, ConfigManifest, ConflictPolicy, Selection};
+use crate::console::{self, say};
+use crate::history::{self, Operation};
+use crate::merge::{self, FileMerge};
+use crate::paths::{self, TargetPaths};
+use crate::privilege::{Escalation, FsOps};
+use crate::prune::{self, RetentionPolicy};
+use crate::safety::{Guard, RestoreLimits};
+use crate::signing::{self, Verdict};
+use crate::template::{self, Vars};
+use crate::ui::{self, Bulk, Line, SelectList, TerminalGuard};
 use chrono::Local;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, Read};
 use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use sha2::{Digest, Sha256};
+use flate2::read::GzDecoder;
 use tar::Archive;
 
-use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-};
+use crossterm::event::{Event, KeyCode, KeyEvent};
 use tui::{
-    backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
-    style::{Modifier, Style},
-    text::Span,
-    widgets::{Block, Borders, List, ListItem, ListState},
-    Terminal,
+    backend::Backend,
+    layout::{Constraint, Direction, Layout, Rect},
+    widgets::{Block, Borders, Paragraph, Wrap},
+    Frame, Terminal,
 };
 
-pub fn restore_configs(archive_path: &str, manifest_path: &str, dry_run: bool) {
+/// Settings shared by the CLI and TUI restore paths
+#[derive(Debug, Clone, Default)]
+pub struct RestoreOptions {
+    pub dry_run: bool,
+    /// Policy forced on every item from the CLI
+    pub policy: Option<ConflictPolicy>,
+    /// Per-item policies from `--policy NAME=POLICY`
+    pub item_policies: HashMap<String, ConflictPolicy>,
+    /// Alternate root and home to restore into
+    pub target: TargetPaths,
+    /// Never use sudo/pkexec; system-level items that need it are skipped
+    pub no_escalate: bool,
+    /// Retention policy, applied after the restore when `auto` is set
+    pub retention: RetentionPolicy,
+    /// Where archives may write and how large they may be
+    pub limits: RestoreLimits,
+    /// Restore archives that are unsigned or not signed by a trusted key
+    pub allow_unsigned: bool,
+    /// Items picked by `--profile` and `--tag`
+    pub selection: Selection,
+    /// Template variables from `--var NAME=VALUE`
+    pub vars: Vars,
+}
+
+impl RestoreOptions {
+    /// CLI overrides win over the manifest, which wins over the default
+    pub fn policy_for(&self, item: &ConfigItem) -> ConflictPolicy {
+        self.item_policies
+            .get(&item.name)
+            .copied()
+            .or(self.policy)
+            .or(item.policy)
+            .unwrap_or_default()
+    }
+}
+
+/// What the restore will do with a single manifest item
+enum ItemAction {
+    Replace,
+    Skip(&'static str),
+    KeepBoth(PathBuf),
+    Merge(Vec<FileMerge>),
+}
+
+/// Most recent modification time anywhere under `path`
+fn newest_mtime(path: &Path) -> Option<SystemTime> {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter_map(|e| e.metadata().ok()?.modified().ok())
+        .max()
+}
+
+fn open_archive(archive_path: &str) -> io::Result<Archive<GzDecoder<fs::File>>> {
+    Ok(Archive::new(GzDecoder::new(fs::File::open(archive_path)?)))
+}
+
+/// A private copy of the archive; the restore checks and unpacks this copy only,
+/// so swapping the file after it was verified changes nothing
+fn snapshot_archive(archive_path: &str) -> io::Result<tempfile::NamedTempFile> {
+    let mut snapshot = tempfile::NamedTempFile::new()?;
+    io::copy(&mut fs::File::open(archive_path)?, snapshot.as_file_mut())?;
+    Ok(snapshot)
+}
+
+/// Index of the first item whose archive root contains `path`, and the part below it
+fn owning_item<'a>(roots: &[PathBuf], path: &'a Path) -> Option<(usize, &'a Path)> {
+    roots
+        .iter()
+        .enumerate()
+        .find_map(|(idx, root)| Some((idx, path.strip_prefix(root).ok()?)))
+}
+
+fn check_entry<R: io::Read>(guard: &mut Guard, entry: &tar::Entry<R>, roots: &[PathBuf]) -> io::Result<()> {
+    let link = entry.link_name()?;
+    guard
+        .check_entry(&entry.path()?, entry.header().entry_type(), entry.size(), link.as_deref(), roots)
+        .map_err(|msg| io::Error::new(io::ErrorKind::InvalidData, msg))
+}
+
+/// What a single read of the archive tells the planner, without writing anything
+#[derive(Default)]
+struct ArchiveScan {
+    /// Newest entry mtime per item; items missing from the archive have none
+    mtimes: HashMap<usize, SystemTime>,
+    /// Text files of items restored with the merge policy
+    texts: HashMap<PathBuf, String>,
+    /// Digest of every entry, checked against the archive's signature
+    digests: BTreeMap<String, String>,
+    /// Bytes each item takes up in the archive
+    sizes: HashMap<usize, u64>,
+}
+
+fn scan_archive(
+    archive_path: &str,
+    roots: &[PathBuf],
+    wants_text: &[bool],
+    guard: &mut Guard,
+) -> io::Result<ArchiveScan> {
+    let mut scan = ArchiveScan::default();
+
+    for entry in open_archive(archive_path)?.entries()? {
+        let mut entry = entry?;
+        check_entry(guard, &entry, roots)?;
+        let path = paths::normalize(&entry.path()?);
+        let key = path.to_string_lossy().to_string();
+        let owner = owning_item(roots, &path).map(|(idx, _)| idx);
+
+        if let Some(idx) = owner {
+            *scan.sizes.entry(idx).or_insert(0) += entry.size();
+            let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(entry.header().mtime().unwrap_or(0));
+            let newest = scan.mtimes.entry(idx).or_insert(mtime);
+            *newest = (*newest).max(mtime);
+        }
+
+        let size = entry.size();
+        let mergeable = owner.is_some_and(|idx| wants_text[idx])
+            && entry.header().entry_type().is_file()
+            && size <= merge::MAX_TEXT_SIZE;
+        let digest = if mergeable {
+            let mut bytes = vec![];
+            entry.read_to_end(&mut bytes)?;
+            if let Some(text) = merge::read_text_from(&bytes[..], size) {
+                scan.texts.insert(path, text);
+            }
+            signing::content_digest(&bytes[..])?
+        } else {
+            signing::entry_digest(&mut entry)?
+        };
+        scan.digests.insert(key, digest);
+    }
+    Ok(scan)
+}
+
+/// Lines of a rendered template shown in previews
+const PREVIEW_LINES: usize = 20;
+
+/// Variables for rendering templates: built-ins, then the host vars file, then `--var`
+fn template_vars(options: &RestoreOptions) -> Result<Vars, String> {
+    let mut vars = template::machine_vars(template::load_host_vars()?);
+    vars.extend(options.vars.clone());
+    Ok(vars)
+}
+
+/// Archived text files of item `idx`, by their path below the item, in order
+fn item_texts<'a>(texts: &'a HashMap<PathBuf, String>, roots: &[PathBuf], idx: usize) -> Vec<(&'a Path, &'a String)> {
+    let mut files: Vec<(&Path, &String)> = texts
+        .iter()
+        .filter_map(|(path, text)| match owning_item(roots, path) {
+            Some((owner, sub)) if owner == idx => Some((sub, text)),
+            _ => None,
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Placeholders in template items that no variable fills
+fn missing_vars(texts: &HashMap<PathBuf, String>, roots: &[PathBuf], templates: &[bool], vars: &Vars) -> Vec<String> {
+    let mut missing = BTreeSet::new();
+    for (path, text) in texts {
+        if owning_item(roots, path).is_some_and(|(idx, _)| templates[idx]) {
+            missing.extend(template::placeholders(text).into_iter().filter(|name| !vars.contains_key(name)));
+        }
+    }
+    missing.into_iter().collect()
+}
+
+/// Render, in place, every text that belongs to a template item
+fn render_templates(texts: &mut HashMap<PathBuf, String>, roots: &[PathBuf], templates: &[bool], vars: &Vars) {
+    for (path, text) in texts.iter_mut() {
+        if owning_item(roots, path).is_some_and(|(idx, _)| templates[idx]) {
+            *text = template::render(text, vars);
+        }
+    }
+}
+
+/// Stream the entries of each item straight to where it is being installed.
+///
+/// `installs[idx]` is the directory or file item `idx` unpacks into; `None` skips its entries.
+fn unpack_items(
+    archive_path: &str,
+    roots: &[PathBuf],
+    installs: &[Option<PathBuf>],
+    guard: &mut Guard,
+) -> io::Result<()> {
+    for entry in open_archive(archive_path)?.entries()? {
+        let mut entry = entry?;
+        // Limits are checked again as the entries are written
+        check_entry(guard, &entry, roots)?;
+        let path = paths::normalize(&entry.path()?);
+        let Some((idx, sub)) = owning_item(roots, &path) else {
+            continue;
+        };
+        let Some(install) = &installs[idx] else {
+            continue;
+        };
+
+        let target = if sub.as_os_str().is_empty() {
+            install.clone()
+        } else {
+            install.join(sub)
+        };
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        entry.unpack(&target)?;
+    }
+    Ok(())
+}
+
+pub fn restore_configs(archive_path: &str, manifest_path: &str, options: &RestoreOptions) -> Result<(), String> {
     if !Path::new(archive_path).exists() || !Path::new(manifest_path).exists() {
-        println!("❌ Archive or manifest not found.\n");
-        println!("Restitch could not find the default archive or manifest file in:");
-        println!("  • {}", archive_path);
-        println!("  • {}\n", manifest_path);
-        std::process::exit(1);
+        return Err(format!(
+            "❌ Archive or manifest not found.\n\nRestitch could not find the default archive or manifest file in:\n  • {}\n  • {}\n",
+            archive_path, manifest_path
+        ));
     }
 
-    println!("📦 Extracting archive...");
-    let tar_gz = fs::File::open(archive_path).expect("❌ Could not open archive file");
-    let decompressor = flate2::read::GzDecoder::new(tar_gz);
-    let mut archive = Archive::new(decompressor);
+    let (mut manifest, manifest_sha256) = read_manifest(Path::new(manifest_path))?;
 
-    fs::create_dir_all("restitch_tmp").expect("❌ Could not create temp extraction directory");
-    archive.unpack("restitch_tmp").expect("❌ Failed to extract archive");
+    // 🏷️ Only the items picked by --profile / --tag
+    if !options.selection.is_empty() {
+        let total = manifest.items.len();
+        manifest.items.retain(|item| options.selection.matches(item));
+        if manifest.items.is_empty() {
+            return Err("❌ No manifest items match the selected profile or tags.".to_string());
+        }
+        say!("🏷️  Restoring {} of {} item(s) picked by profile/tags\n", manifest.items.len(), total);
+    }
 
-    println!("📂 Extracted to: restitch_tmp/\n");
+    restore_manifest(archive_path, manifest, manifest_sha256, options)
+}
 
-    let manifest_str = fs::read_to_string(manifest_path).expect("❌ Could not read manifest file");
-    let manifest: ConfigManifest = toml::from_str(&manifest_str).expect("❌ Invalid manifest format");
+/// Restore the items of an already loaded manifest; `manifest_sha256` identifies it in `backup.toml`
+pub fn restore_manifest(
+    archive_path: &str,
+    manifest: ConfigManifest,
+    manifest_sha256: String,
+    options: &RestoreOptions,
+) -> Result<(), String> {
+    // Dry runs and cancelled restores change nothing, so they stay out of the history
+    let Some(result) = restore_items(archive_path, manifest, manifest_sha256, options).transpose() else {
+        return Ok(());
+    };
+    history::record(Operation::Restore, archive_path, &result);
+    result.map(|_| ())
+}
 
-    println!("🧭 Restore Plan{}:", if dry_run { " (dry-run)" } else { "" });
-    println!("───────────────────────────────────────────────");
+/// The restore itself; returns a summary once files were written
+fn restore_items(
+    archive_path: &str,
+    manifest: ConfigManifest,
+    manifest_sha256: String,
+    options: &RestoreOptions,
+) -> Result<Option<String>, String> {
+    let dry_run = options.dry_run;
+
+    // 📦 One pass over the archive to learn what it holds; nothing touches disk yet
+    say!("📦 Reading archive...\n");
+    let roots: Vec<PathBuf> = manifest.items.iter().map(|item| paths::archive_rel(&item.path)).collect();
+    let wants_text: Vec<bool> = manifest
+        .items
+        .iter()
+        .map(|item| item.template || options.policy_for(item) == ConflictPolicy::Merge)
+        .collect();
+    let mut guard = Guard::new(&options.limits).map_err(|e| format!("❌ Invalid [restore] limits: {}", e))?;
+    let snapshot = snapshot_archive(archive_path).map_err(|e| format!("❌ Could not read archive '{}': {}", archive_path, e))?;
+    let source = snapshot.path().to_string_lossy().to_string();
+    let mut scan = scan_archive(&source, &roots, &wants_text, &mut guard.restart())
+        .map_err(|e| format!("❌ Refusing to restore '{}': {}", archive_path, e))?;
+
+    // 🔏 Only archives signed by a trusted key are restored, unless explicitly allowed
+    let problem = match signing::verify(Path::new(archive_path), &manifest_sha256, &scan.digests) {
+        Verdict::Trusted(name) => {
+            say!("🔏 Signed by trusted key: {}\n", name);
+            None
+        }
+        Verdict::Unsigned => Some("archive is not signed".to_string()),
+        Verdict::Untrusted(reason) => Some(reason),
+    };
+    if let Some(problem) = problem {
+        if !options.allow_unsigned {
+            return Err(format!(
+                "❌ Refusing to restore '{}': {}\n💡 Trust the signer with `restitch trust <key>`, or pass --allow-unsigned.",
+                archive_path, problem
+            ));
+        }
+        say!("⚠️  {}, restoring anyway (--allow-unsigned)\n", problem);
+    }
 
-    let home = dirs::home_dir().expect("Could not get home directory");
-    let timestamp = Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
-    let backup_dir = PathBuf::from("backups").join(&timestamp);
+    // 🧩 Render templates before planning, so merges and the plan see the final text
+    let templates: Vec<bool> = manifest.items.iter().map(|item| item.template).collect();
+    let mut vars = if templates.contains(&true) { template_vars(options)? } else { Vars::new() };
+    let missing = missing_vars(&scan.texts, &roots, &templates, &vars);
+    if !missing.is_empty() {
+        if dry_run {
+            say!("⚠️  No value for {}; placeholders are left in (pass --var NAME=VALUE)\n", missing.join(", "));
+        } else {
+            template::prompt_missing(&missing, &mut vars);
+            say!();
+        }
+    }
+    render_templates(&mut scan.texts, &roots, &templates, &vars);
 
-    for item in &manifest.items {
-        let rel_path = Path::new(&item.path)
-            .strip_prefix(home.to_str().unwrap())
-            .unwrap_or(Path::new(&item.path));
-        let backup_path = backup_dir.join(rel_path);
+    say!("🧭 Restore Plan{}:", if dry_run { " (dry-run)" } else { "" });
+    say!("───────────────────────────────────────────────");
 
-        let is_dir = fs::metadata(&item.path)
+    let target = &options.target;
+    let timestamp = Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+    let mut backup = Backup::new(&timestamp);
+
+    // 🔀 Previous export acts as the common ancestor for three-way merges
+    let base = if manifest.items.iter().any(|item| options.policy_for(item) == ConflictPolicy::Merge) {
+        let base_path = merge::base_archive_path(Path::new(archive_path));
+        if !base_path.exists() {
+            say!("⚠️  No previous export at {}, merging is unavailable.\n", base_path.display());
+        }
+        let mut base = merge::load_base(&base_path);
+        render_templates(&mut base, &roots, &templates, &vars);
+        base
+    } else {
+        Default::default()
+    };
+
+    // 🔒 Only system-level items are ever written through sudo/pkexec
+    let escalation = if manifest.items.iter().any(|item| paths::is_system(&item.path)) {
+        Escalation::detect(!options.no_escalate)
+    } else {
+        Escalation::Unavailable
+    };
+
+    let mut plan: Vec<(&ConfigItem, ItemAction, FsOps)> = vec![];
+
+    for (idx, item) in manifest.items.iter().enumerate() {
+        let rel_path = &roots[idx];
+        let dest = target.dest_for(&item.path);
+        let backup_path = backup.file_path(rel_path);
+
+        let exists = dest.exists();
+        let is_dir = fs::metadata(&dest)
             .map(|meta| meta.is_dir())
             .unwrap_or(false);
 
-        println!(
-            "🔁 REPLACE: {} → {}\n   ↪ Backup will be created at: {}{}",
-            item.name,
-            item.path,
-            backup_path.display(),
-            if is_dir {
-                "\n   ⚠️  This is a directory and all its contents will be restored recursively"
-            } else {
-                ""
+        let ops = if paths::is_system(&item.path) {
+            escalation.ops_for(&dest)
+        } else {
+            Some(FsOps::direct())
+        };
+
+        let policy = options.policy_for(item);
+        let rejected = guard.check_item(&item.path);
+        let action = if let Err(reason) = rejected {
+            ItemAction::Skip(reason)
+        } else {
+            match policy {
+                _ if ops.is_none() => ItemAction::Skip("needs root, and sudo/pkexec is unavailable"),
+                _ if !scan.mtimes.contains_key(&idx) => ItemAction::Skip("missing from archive"),
+                ConflictPolicy::Overwrite => ItemAction::Replace,
+                ConflictPolicy::SkipIfExists if exists => ItemAction::Skip("already exists"),
+                ConflictPolicy::SkipIfExists => ItemAction::Replace,
+                ConflictPolicy::KeepBoth if exists => {
+                    ItemAction::KeepBoth(PathBuf::from(format!("{}.restitch-new", dest.display())))
+                }
+                ConflictPolicy::KeepBoth => ItemAction::Replace,
+                ConflictPolicy::NewerWins => {
+                    let live = newest_mtime(&dest);
+                    if exists && live >= scan.mtimes.get(&idx).copied() {
+                        ItemAction::Skip("local copy is newer")
+                    } else {
+                        ItemAction::Replace
+                    }
+                }
+                ConflictPolicy::Merge => {
+                    ItemAction::Merge(merge::plan_merges(&scan.texts, rel_path, &dest, &base))
+                }
             }
-        );
+        };
+
+        match &action {
+            ItemAction::Skip(reason) if rejected.is_err() => {
+                say!("🚫 REJECTED: {} → {} ({})", item.name, dest.display(), reason);
+            }
+            ItemAction::Skip(reason) if ops.is_none() => {
+                say!("🔒 REFUSED: {} → {} ({})", item.name, dest.display(), reason);
+            }
+            ItemAction::Skip(reason) => {
+                say!("⏭️  SKIP: {} → {} ({}, policy: {})", item.name, dest.display(), reason, policy);
+            }
+            ItemAction::KeepBoth(new_path) => {
+                say!(
+                    "🪞 KEEP BOTH: {} → {}\n   ↪ Archived copy will be written to: {}",
+                    item.name,
+                    dest.display(),
+                    new_path.display(),
+                );
+            }
+            ItemAction::Merge(merges) if !merges.is_empty() => {
+                say!(
+                    "🔀 MERGE: {} → {}\n   ↪ Backup will be created at: {}",
+                    item.name,
+                    dest.display(),
+                    backup_path.display(),
+                );
+                for m in merges {
+                    let conflicts = m.result.conflicts();
+                    if conflicts == 0 {
+                        say!("   ✅ {} merges cleanly", m.dest.display());
+                    } else {
+                        say!("   ⚠️  {} has {} conflict(s)", m.dest.display(), conflicts);
+                    }
+                }
+            }
+            ItemAction::Replace | ItemAction::Merge(_) => {
+                say!(
+                    "🔁 REPLACE: {} → {}\n   ↪ Backup will be created at: {}{}",
+                    item.name,
+                    dest.display(),
+                    backup_path.display(),
+                    if is_dir {
+                        "\n   ⚠️  This is a directory and all its contents will be restored recursively"
+                    } else {
+                        ""
+                    }
+                );
+            }
+        }
+
+        if item.template && !matches!(action, ItemAction::Skip(_)) {
+            let files = item_texts(&scan.texts, &roots, idx);
+            say!("   🧩 Template: {} text file(s) rendered for this host", files.len());
+            if dry_run {
+                for (sub, text) in files {
+                    let shown = if sub.as_os_str().is_empty() { dest.clone() } else { dest.join(sub) };
+                    say!("   ┌ {}", shown.display());
+                    for line in text.lines().take(PREVIEW_LINES) {
+                        say!("   │ {}", line);
+                    }
+                    let more = text.lines().count().saturating_sub(PREVIEW_LINES);
+                    if more > 0 {
+                        say!("   │ … {} more line(s)", more);
+                    }
+                }
+            }
+        }
+
+        let ops = ops.unwrap_or_else(FsOps::direct);
+        if ops.is_escalated() && !matches!(action, ItemAction::Skip(_)) {
+            say!("   🔒 Needs elevated privileges, will use {}", escalation.label());
+        }
+
+        plan.push((item, action, ops));
     }
 
     if dry_run {
-        println!("\n🔎 Restore dry-run complete.");
-        return;
+        say!("\n🔎 Restore dry-run complete.");
+        return Ok(None);
     }
 
     // 🛑 Prompt confirmation before continuing
-    println!("\n⚠️  This operation will overwrite the above config files.");
-    print!("Proceed with restore? [y/N]: ");
-    io::stdout().flush().unwrap();
-
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
-    if input.trim().to_lowercase() != "y" {
-        println!("\n❌ Restore cancelled.");
-        return;
+    say!("\n⚠️  This operation will overwrite the above config files.");
+    if !console::confirm("Proceed with restore?") {
+        say!("\n❌ Restore cancelled.");
+        return Ok(None);
     }
 
-    // 🛠️ Perform actual restore
-    for item in &manifest.items {
-        let rel_path = Path::new(&item.path)
-            .strip_prefix(home.to_str().unwrap())
-            .unwrap_or(Path::new(&item.path));
-        let backup_path = backup_dir.join(rel_path);
-
-        fs::create_dir_all(backup_path.parent().unwrap())
-            .expect("❌ Could not create backup directory");
+    // 🧩 Settle conflicts before anything is written
+    let mut merges: Vec<FileMerge> = vec![];
+    let mut merge_ops: Vec<FsOps> = vec![];
+    for (_, action, ops) in plan.iter_mut() {
+        if let ItemAction::Merge(m) = action {
+            merge_ops.extend(std::iter::repeat_n(*ops, m.len()));
+            merges.append(m);
+        }
+    }
 
-        if Path::new(&item.path).exists() {
-            fs::rename(&item.path, &backup_path)
-                .expect("❌ Failed to back up existing file");
+    let conflicted = merges.iter().filter(|m| m.result.conflicts() > 0).count();
+    if conflicted > 0 {
+        say!();
+        if console::confirm(&format!("🔀 Resolve {} conflicted file(s) interactively?", conflicted)) {
+            console::resolve(&mut merges).map_err(|e| format!("❌ Conflict resolver failed: {}", e))?;
         }
+    }
+
+    // 🛠️ Perform actual restore: move the live copies aside first...
+    let needs_staging = plan
+        .iter()
+        .any(|(_, action, ops)| ops.is_escalated() && !matches!(action, ItemAction::Skip(_)));
+    // Private scratch space for items only sudo/pkexec may put in place; removed when dropped
+    let staging = needs_staging
+        .then(|| tempfile::Builder::new().prefix("restitch-").tempdir())
+        .transpose()
+        .map_err(|e| format!("❌ Could not create staging directory: {}", e))?;
+
+    // Per item: where its entries are unpacked, and where they finally belong
+    let mut installs: Vec<Option<PathBuf>> = vec![None; plan.len()];
+    let mut dests: Vec<Option<PathBuf>> = vec![None; plan.len()];
+
+    for (idx, (item, action, ops)) in plan.iter().enumerate() {
+        let rel_path = &roots[idx];
+
+        let (dest, backup_rel) = match action {
+            ItemAction::Skip(_) => continue,
+            ItemAction::KeepBoth(new_path) => {
+                let mut rel = rel_path.clone().into_os_string();
+                rel.push(".restitch-new");
+                (new_path.clone(), PathBuf::from(rel))
+            }
+            ItemAction::Replace | ItemAction::Merge(_) => (target.dest_for(&item.path), rel_path.clone()),
+        };
+
+        // 🗂️ Everything changed here is journaled so `--revert` can undo it exactly
+        backup.create_dirs(dest.parent().unwrap(), ops)
+            .map_err(|e| format!("❌ Could not create destination directory: {}", e))?;
+
+        backup.stash(&dest, &backup_rel, ops)
+            .map_err(|e| format!("❌ Failed to back up existing file: {}", e))?;
 
-        let extracted_path = Path::new("restitch_tmp").join(rel_path);
-        fs::create_dir_all(Path::new(&item.path).parent().unwrap())
-            .expect("❌ Could not create destination directory");
+        installs[idx] = match &staging {
+            Some(staging) if ops.is_escalated() => Some(staging.path().join(idx.to_string())),
+            _ => Some(dest.clone()),
+        };
+        dests[idx] = Some(dest);
+    }
+
+    // ...then stream the selected items out of the archive
+    unpack_items(&source, &roots, &installs, &mut guard).map_err(|e| format!("❌ Failed to extract archive: {}", e))?;
+
+    for (idx, (item, _, ops)) in plan.iter().enumerate() {
+        let (Some(install), Some(dest)) = (&installs[idx], &dests[idx]) else {
+            continue;
+        };
+        if install != dest {
+            ops.copy(install, dest).map_err(|e| format!("❌ Failed to copy into place: {}", e))?;
+        }
+        if let Some(owner) = &item.owner {
+            ops.apply_ownership(dest, owner)
+                .map_err(|e| format!("❌ Failed to restore ownership: {}", e))?;
+        }
+    }
+    drop(staging);
+
+    // 🧩 Rendered text goes over the raw template files
+    for (path, text) in &scan.texts {
+        let Some((idx, sub)) = owning_item(&roots, path) else {
+            continue;
+        };
+        let (Some(dest), (item, _, ops)) = (&dests[idx], &plan[idx]) else {
+            continue;
+        };
+        if item.template {
+            let file = if sub.as_os_str().is_empty() { dest.clone() } else { dest.join(sub) };
+            ops.write(&file, text).map_err(|e| format!("❌ Failed to write rendered template: {}", e))?;
+        }
+    }
 
-        if extracted_path.is_dir() {
-            copy_dir_recursive(&extracted_path, Path::new(&item.path))
-                .expect("❌ Failed to copy directory");
+    // 🔀 Write merged results over the freshly restored copies
+    for (m, ops) in merges.iter().zip(&merge_ops) {
+        ops.write(&m.dest, &m.result.render()).map_err(|e| format!("❌ Failed to write merged file: {}", e))?;
+        let unresolved = m.result.unresolved();
+        if unresolved > 0 {
+            say!("⚠️  {} conflict(s) left with markers in {}", unresolved, m.dest.display());
         } else {
-            fs::copy(&extracted_path, &item.path)
-                .expect("❌ Failed to copy file");
+            say!("🔀 Merged: {}", m.dest.display());
         }
     }
 
-    println!("\n✅ Restore completed successfully.");
-    println!("📁 Backups saved to: backups/{}/", timestamp);
+    let restored: Vec<String> = plan
+        .iter()
+        .filter(|(_, action, _)| !matches!(action, ItemAction::Skip(_)))
+        .map(|(item, _, _)| item.name.clone())
+        .collect();
+    let mut summary = format!("{} of {} item(s) restored", restored.len(), plan.len());
+    backup.finish(BackupMeta {
+        created: timestamp.clone(),
+        source_archive: archive_path.to_string(),
+        manifest_sha256,
+        hostname: host::hostname(),
+        items: restored,
+        ..Default::default()
+    })
+    .map_err(|e| format!("❌ Failed to write backup metadata: {}", e))?;
+
+    say!("\n✅ Restore completed successfully.");
+    if backup.dir.exists() {
+        say!("📁 Backups saved to: {}/", backup.dir.display());
+        summary.push_str(&format!(", backups in {}/", backup.dir.display()));
+    }
+
+    // 🧹 Keep the backups directory in check if the targets file asks for it
+    if options.retention.auto {
+        say!();
+        if let Err(e) = prune::prune(&options.retention, false, false) {
+            say!("⚠️  Automatic prune failed: {}", e);
+        }
+    }
+    Ok(Some(summary))
 }
 
 pub fn copy_dir_recursive(src: &Path, dst: &Path) -> io::Result<()> {
@@ -135,126 +645,247 @@ pub fn copy_dir_recursive(src: &Path, dst: &Path) -> io::Result<()> {
     Ok(())
 }
 
-pub fn run_restore_ui(manifest_path: &str, archive_path: &str, dry_run: bool) -> io::Result<()> {
-    let manifest_str = fs::read_to_string(manifest_path)?;
+/// Rendered templates and sizes of the items, shown next to them in the restore TUI
+type ItemDetails = (Vec<Option<String>>, Vec<u64>);
+
+/// Everything the restore TUI shows about the items, from one read of the archive
+fn item_details(archive_path: &str, items: &[ConfigItem], options: &RestoreOptions) -> ItemDetails {
+    let templates: Vec<bool> = items.iter().map(|item| item.template).collect();
+    let roots: Vec<PathBuf> = items.iter().map(|item| paths::archive_rel(&item.path)).collect();
+    // A hostile archive is refused by the restore itself; the TUI just shows nothing for it
+    let scan = Guard::new(&options.limits)
+        .ok()
+        .and_then(|mut guard| scan_archive(archive_path, &roots, &templates, &mut guard).ok());
+    let Some(mut scan) = scan else {
+        return (vec![None; items.len()], vec![0; items.len()]);
+    };
+    let sizes = (0..items.len()).map(|idx| scan.sizes.get(&idx).copied().unwrap_or(0)).collect();
+    (template_previews(&mut scan, &roots, items, &templates, options), sizes)
+}
+
+/// Rendered text of each template item; `None` for other items
+fn template_previews(
+    scan: &mut ArchiveScan,
+    roots: &[PathBuf],
+    items: &[ConfigItem],
+    templates: &[bool],
+    options: &RestoreOptions,
+) -> Vec<Option<String>> {
+    // A broken vars file is reported by the restore itself
+    let vars = match template_vars(options) {
+        Ok(vars) if templates.contains(&true) => vars,
+        _ => return vec![None; items.len()],
+    };
+    let missing = missing_vars(&scan.texts, roots, templates, &vars);
+    render_templates(&mut scan.texts, roots, templates, &vars);
+
+    (0..items.len())
+        .map(|idx| {
+            templates[idx].then(|| {
+                let mut preview = String::new();
+                if !missing.is_empty() {
+                    preview.push_str(&format!("⚠️ Asked for on restore: {}\n\n", missing.join(", ")));
+                }
+                for (sub, text) in item_texts(&scan.texts, roots, idx) {
+                    let name = if sub.as_os_str().is_empty() { items[idx].path.clone() } else { sub.display().to_string() };
+                    preview.push_str(&format!("── {} ──\n{}\n", name, text));
+                }
+                preview
+            })
+        })
+        .collect()
+}
+
+/// Read a manifest, along with the digest that identifies it in `backup.toml`
+pub fn read_manifest(manifest_path: &Path) -> Result<(ConfigManifest, String), String> {
+    let manifest_str = fs::read_to_string(manifest_path)
+        .map_err(|e| format!("❌ Could not read manifest '{}': {}", manifest_path.display(), e))?;
     let manifest: ConfigManifest = toml::from_str(&manifest_str)
-        .expect("❌ Invalid manifest format");
+        .map_err(|e| format!("❌ Invalid manifest format in '{}': {}", manifest_path.display(), e.message()))?;
+    Ok((manifest, format!("{:x}", Sha256::digest(manifest_str.as_bytes()))))
+}
 
-    let mut items: Vec<ConfigItem> = manifest.items
+/// Restore what was ticked in an `ItemPicker`, with the policies picked there
+pub fn restore_picked(
+    archive_path: &str,
+    manifest_sha256: String,
+    items: Vec<ConfigItem>,
+    options: &RestoreOptions,
+) -> Result<(), String> {
+    let selected_items: Vec<ConfigItem> = items
         .into_iter()
-        .map(|mut item| { item.selected = true; item })
+        .filter(|i| i.selected)
         .collect();
 
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-    let result = ui_loop(&mut terminal, &mut items, dry_run);
-
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
-
-    if let Ok(true) = result {
-        let selected_items: Vec<ConfigItem> = items
-            .into_iter()
-            .filter(|i| i.selected)
-            .collect();
+    if selected_items.is_empty() {
+        say!("❌ No items selected.");
+        return Ok(());
+    }
 
-        if selected_items.is_empty() {
-            println!("❌ No items selected.");
-        } else {
-            let temp_manifest = ConfigManifest { items: selected_items };
-            let temp_manifest_str = toml::to_string(&temp_manifest).unwrap();
-            fs::write("restitch_tmp_selected.manifest.toml", &temp_manifest_str)?;
-            restore_configs(archive_path, "restitch_tmp_selected.manifest.toml", dry_run);
-        }
+    // Policies picked in the TUI are now part of the manifest
+    let options = RestoreOptions {
+        dry_run: options.dry_run,
+        target: options.target.clone(),
+        no_escalate: options.no_escalate,
+        retention: options.retention.clone(),
+        limits: options.limits.clone(),
+        allow_unsigned: options.allow_unsigned,
+        vars: options.vars.clone(),
+        ..Default::default()
+    };
+    restore_manifest(archive_path, ConfigManifest { items: selected_items }, manifest_sha256, &options)
+}
+
+pub fn run_restore_ui(manifest_path: &str, archive_path: &str, options: &RestoreOptions) -> Result<(), String> {
+    let (manifest, manifest_sha256) = read_manifest(Path::new(manifest_path))?;
+
+    let mut picker = ItemPicker::new(archive_path, manifest.items, options);
+
+    let ui_error = |e: io::Error| format!("❌ Restore UI error: {}", e);
+    let mut terminal = TerminalGuard::enter().map_err(ui_error)?;
+    let confirmed = ui_loop(&mut terminal, &mut picker).map_err(ui_error)?;
+    drop(terminal);
+
+    if confirmed {
+        restore_picked(archive_path, manifest_sha256, picker.items, options)?;
     }
 
     Ok(())
 }
 
-fn ui_loop<B: tui::backend::Backend>(
-    terminal: &mut Terminal<B>,
-    items: &mut Vec<ConfigItem>,
+/// Checkbox list of a manifest's items, with rendered templates in a side pane
+pub struct ItemPicker {
+    pub items: Vec<ConfigItem>,
+    previews: Vec<Option<String>>,
+    sizes: Vec<u64>,
+    /// Previews and sizes, while the archive is still being read on a background thread
+    pending: Option<Receiver<ItemDetails>>,
+    list: SelectList,
     dry_run: bool,
-) -> io::Result<bool> {
-    // Handle config loading errors BEFORE enabling terminal features
-    let mut state = ListState::default();
-    if !items.is_empty() {
-        state.select(Some(0));
+}
+
+impl ItemPicker {
+    /// Items picked by `--profile`/`--tag` start out ticked, each with the policy it restores with
+    pub fn new(archive_path: &str, items: Vec<ConfigItem>, options: &RestoreOptions) -> Self {
+        let items: Vec<ConfigItem> = items
+            .into_iter()
+            .map(|mut item| {
+                item.selected = options.selection.matches(&item);
+                item.policy = Some(options.policy_for(&item));
+                item
+            })
+            .collect();
+
+        // Reading a large archive takes a while, so the list is usable before it is done
+        let (sender, pending) = mpsc::channel();
+        let (archive_path, scanned, scan_options) = (archive_path.to_string(), items.clone(), options.clone());
+        thread::spawn(move || {
+            let _ = sender.send(item_details(&archive_path, &scanned, &scan_options));
+        });
+
+        let previews = items.iter().map(|item| item.template.then(|| "⏳ Rendering…".to_string())).collect();
+        let sizes = vec![0; items.len()];
+        ItemPicker { items, previews, sizes, pending: Some(pending), list: SelectList::default(), dry_run: options.dry_run }
+    }
+
+    pub fn draw<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
+        let lines = self
+            .items
+            .iter()
+            .map(|item| {
+                let policy = item.policy.unwrap_or_default();
+                let template = if item.template { "  🧩" } else { "" };
+                Line::new(format!("{} ", ui::checkbox(item.selected)), format!("{}  ({}){}", item.name, policy, template))
+            })
+            .collect();
+        self.list.set_lines(lines);
+        if let Some(Ok((previews, sizes))) = self.pending.as_ref().map(Receiver::try_recv) {
+            (self.previews, self.sizes) = (previews, sizes);
+            self.pending = None;
+        }
+        let picked = self.items.iter().filter(|item| item.selected).count();
+        let picked_size = self.items.iter().zip(&self.sizes).filter(|(item, _)| item.selected).map(|(_, size)| size).sum();
+        let mut status = ui::picked_status(picked, self.items.len(), picked_size);
+        if self.pending.is_some() {
+            status.push_str(" (counting…)");
+        }
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(3)].as_ref())
+            .split(area);
+
+        let title = "🌀 Restitch: Restore Configs";
+
+        // 🧩 Templates get a side pane with the text as it will be written
+        if self.previews.iter().any(Option::is_some) {
+            let panes = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(45), Constraint::Percentage(55)].as_ref())
+                .split(chunks[0]);
+            self.list.render(f, panes[0], title);
+
+            let preview = self
+                .list
+                .selected()
+                .and_then(|i| self.previews[i].clone())
+                .unwrap_or_else(|| "Not a template.".to_string());
+            let pane = Paragraph::new(preview)
+                .block(Block::default().title("🧩 Rendered Preview").borders(Borders::ALL))
+                .wrap(Wrap { trim: false });
+            f.render_widget(pane, panes[1]);
+        } else {
+            self.list.render(f, chunks[0], title);
+        }
+
+        let help_text = if self.dry_run {
+            "␣: Toggle  c: Cycle policy  enter: Run Dry-run  q: Quit  /: Search  a/n/i: All/None/Invert"
+        } else {
+            "␣: Toggle  c: Cycle policy  enter: Restore  q: Quit  /: Search  a/n/i: All/None/Invert"
+        };
+        ui::help_bar(f, chunks[1], help_text, &status);
     }
 
+    /// Ticking and policies are handled here; `Enter`, `q` and other keys come back
+    pub fn handle(&mut self, event: Event) -> Option<KeyEvent> {
+        let key = self.list.handle(event)?;
+        if let Some(bulk) = Bulk::from_key(key.code) {
+            for i in self.list.visible() {
+                bulk.apply(&mut self.items[i].selected);
+            }
+            return None;
+        }
+        match key.code {
+            KeyCode::Char(' ') => {
+                if let Some(i) = self.list.selected() {
+                    self.items[i].selected = !self.items[i].selected;
+                }
+            }
+            KeyCode::Char('c') => {
+                if let Some(i) = self.list.selected() {
+                    self.items[i].policy = Some(self.items[i].policy.unwrap_or_default().next());
+                }
+            }
+            _ => return Some(key),
+        }
+        None
+    }
+}
+
+fn ui_loop<B: Backend>(terminal: &mut Terminal<B>, picker: &mut ItemPicker) -> io::Result<bool> {
     loop {
         terminal.draw(|f| {
             let size = f.size();
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([Constraint::Min(1), Constraint::Length(2)].as_ref())
-                .split(size);
-
-            let list_items: Vec<ListItem> = items
-                .iter()
-                .enumerate()
-                .map(|(i, item)| {
-                    let prefix = if item.selected { "[x]" } else { "[ ]" };
-                    let line = format!("{} {}", prefix, item.name);
-                    let style = if state.selected() == Some(i) {
-                        Style::default().add_modifier(Modifier::REVERSED)
-                    } else {
-                        Style::default()
-                    };
-                    ListItem::new(Span::raw(line)).style(style)
-                })
-                .collect();
-
-            let list = List::new(list_items)
-                .block(Block::default().title("🌀 Restitch: Restore Configs").borders(Borders::ALL))
-                .highlight_symbol(">>");
-
-            f.render_stateful_widget(list, chunks[0], &mut state);
-
-            let help_text = if dry_run {
-                "↑↓: Navigate  ␣: Toggle  enter: Run Dry-run  q: Quit"
-            } else {
-                "↑↓: Navigate  ␣: Toggle  enter: Restore  q: Quit"
-            };
-
-            let help = Block::default().title(help_text).borders(Borders::ALL);
-            f.render_widget(help, chunks[1]);
+            picker.draw(f, size);
         })?;
 
-        if event::poll(std::time::Duration::from_millis(200))? {
-            match event::read()? {
-                Event::Key(key) => match key.code {
-                    KeyCode::Char('q') => return Ok(false),
-                    KeyCode::Enter => return Ok(true),
-                    KeyCode::Down => {
-                        if let Some(i) = state.selected() {
-                            let next = if i >= items.len() - 1 { 0 } else { i + 1 };
-                            state.select(Some(next));
-                        }
-                    }
-                    KeyCode::Up => {
-                        if let Some(i) = state.selected() {
-                            let prev = if i == 0 { items.len() - 1 } else { i - 1 };
-                            state.select(Some(prev));
-                        }
-                    }
-                    KeyCode::Char(' ') => {
-                        if let Some(i) = state.selected() {
-                            items[i].selected = !items[i].selected;
-                        }
-                    }
-                    _ => {}
-                },
-                _ => {}
-            }
+        let Some(key) = ui::next_event()?.and_then(|event| picker.handle(event)) else {
+            continue;
+        };
+        match key.code {
+            KeyCode::Char('q') => return Ok(false),
+            KeyCode::Enter => return Ok(true),
+            _ => {}
         }
     }
 }
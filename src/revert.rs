@@ -1,151 +1,698 @@
+use crate::backup::{self, Backup, BackupKind, BackupMeta, ChangeKind};
+use crate::host;
+use crate::config::ConfigItem;
+use crate::console::{self, say};
+use crate::history::{self, Operation};
+use crate::paths::{self, TargetPaths};
+use crate::privilege::{Escalation, FsOps};
+use crate::ui::{self, Bulk, Line, SelectList, TerminalGuard};
+
+use chrono::Local;
+use std::collections::HashMap;
 use std::fs;
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-};
+use crossterm::event::{Event, KeyCode, KeyEvent};
 use tui::{
-    backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
-    style::{Modifier, Style},
-    text::Span,
-    widgets::{Block, Borders, List, ListItem, ListState},
-    Terminal,
+    backend::Backend,
+    layout::{Constraint, Direction, Layout, Rect},
+    widgets::{Block, Borders, Paragraph},
+    Frame, Terminal,
 };
 
-pub fn restore_backup_dir(backup_dir: &PathBuf) -> std::io::Result<()> {
-    for entry in fs::read_dir(backup_dir)? {
-        let entry = entry?;
-        let entry_path = entry.path().to_path_buf();
-        let rel_path = entry_path.strip_prefix(backup_dir).unwrap();
-        let dest = dirs::home_dir().unwrap().join(rel_path);
+/// A single undo action derived from a backup
+#[derive(Clone)]
+pub enum RevertStep {
+    /// Created by the restore, so it goes away
+    Delete { path: PathBuf, system: bool },
+    /// Put the original copy back
+    Restore { from: PathBuf, to: PathBuf, system: bool },
+    /// Directory added by the restore, removed if nothing else landed in it
+    RemoveDir { path: PathBuf, system: bool },
+    /// Directory removed by an earlier revert, created again
+    CreateDir { path: PathBuf, system: bool },
+}
+
+impl RevertStep {
+    fn system(&self) -> bool {
+        match self {
+            RevertStep::Delete { system, .. }
+            | RevertStep::Restore { system, .. }
+            | RevertStep::RemoveDir { system, .. }
+            | RevertStep::CreateDir { system, .. } => *system,
+        }
+    }
+
+    /// Bytes the step puts back or deletes
+    fn size(&self) -> u64 {
+        match self {
+            RevertStep::Restore { from: path, .. } | RevertStep::Delete { path, .. } => backup::tree_stats(path).1,
+            RevertStep::RemoveDir { .. } | RevertStep::CreateDir { .. } => 0,
+        }
+    }
+
+    fn target(&self) -> &Path {
+        match self {
+            RevertStep::Delete { path, .. }
+            | RevertStep::RemoveDir { path, .. }
+            | RevertStep::CreateDir { path, .. } => path,
+            RevertStep::Restore { to, .. } => to,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            RevertStep::Delete { path, .. } => format!("🗑️  DELETE: {} (created by restore)", path.display()),
+            RevertStep::Restore { from, to, .. } => {
+                format!("♻️  RESTORE: {} ← {}", to.display(), from.display())
+            }
+            RevertStep::RemoveDir { path, .. } => {
+                format!("📁 REMOVE DIR: {} (if empty)", path.display())
+            }
+            RevertStep::CreateDir { path, .. } => format!("📁 CREATE DIR: {}", path.display()),
+        }
+    }
+}
+
+/// Work out what reverting a backup will do.
+///
+/// Journaled backups are replayed in reverse; older backups without a journal
+/// fall back to copying every file they contain back into place.
+pub fn plan_revert(backup_dir: &Path, target: &TargetPaths) -> io::Result<Vec<RevertStep>> {
+    if let Some(journal) = backup::load_journal(backup_dir)? {
+        return Ok(journal
+            .entries
+            .iter()
+            .rev()
+            .map(|entry| {
+                let (path, system) = (entry.path.clone(), entry.system);
+                match (entry.kind, &entry.backup) {
+                    (ChangeKind::Replaced, Some(rel)) => RevertStep::Restore {
+                        from: backup_dir.join(rel),
+                        to: path,
+                        system,
+                    },
+                    (ChangeKind::DirAdded, _) => RevertStep::RemoveDir { path, system },
+                    (ChangeKind::DirRemoved, _) => RevertStep::CreateDir { path, system },
+                    _ => RevertStep::Delete { path, system },
+                }
+            })
+            .collect());
+    }
+
+    let mut steps = vec![];
+    for entry in walkdir::WalkDir::new(backup_dir).min_depth(1) {
+        let entry = entry.map_err(io::Error::from)?;
+        let rel_path = entry.path().strip_prefix(backup_dir).unwrap();
+        // The sidecars describe the backup; they were never part of the restored tree
+        if entry.file_type().is_dir() || rel_path == Path::new(backup::JOURNAL_FILE) || rel_path == Path::new(backup::META_FILE) {
+            continue;
+        }
+        steps.push(RevertStep::Restore {
+            from: entry.path().to_path_buf(),
+            to: target.dest_for_archive_rel(rel_path),
+            system: rel_path.starts_with(paths::ROOT_PREFIX),
+        });
+    }
+    Ok(steps)
+}
 
-        if entry.file_type()?.is_dir() {
-            fs::create_dir_all(&dest)?;
-            restore_backup_dir(&entry.path())?;
+/// Apply revert steps, using sudo/pkexec only for system-level entries.
+///
+/// Everything a revert overwrites or deletes is first moved into a fresh backup of its own,
+/// journaled exactly like a restore, so the revert can be reverted too.
+pub fn apply_revert(steps: &[RevertStep], escalation: Escalation, source: &Path) -> io::Result<Backup> {
+    let timestamp = Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+    let mut snapshot = Backup::new(&timestamp);
+
+    for step in steps {
+        let ops = if step.system() {
+            match escalation.ops_for(step.target()) {
+                Some(ops) => ops,
+                None => {
+                    say!("🔒 Skipped (needs root): {}", step.target().display());
+                    continue;
+                }
+            }
         } else {
-            if let Some(parent) = dest.parent() {
-                fs::create_dir_all(parent)?;
+            FsOps::direct()
+        };
+
+        let snapshot_rel = paths::archive_rel(&step.target().to_string_lossy());
+
+        match step {
+            RevertStep::Delete { path, .. } => {
+                if path.symlink_metadata().is_ok() {
+                    snapshot.stash(path, &snapshot_rel, &ops)?;
+                    say!("🗑️  Deleted: {}", path.display());
+                }
+            }
+            RevertStep::Restore { from, to, .. } => {
+                if let Some(parent) = to.parent() {
+                    snapshot.create_dirs(parent, &ops)?;
+                }
+                snapshot.stash(to, &snapshot_rel, &ops)?;
+                ops.copy(from, to)?;
+                say!("🔁 Restored: {}", to.display());
+            }
+            RevertStep::RemoveDir { path, .. } => {
+                if path.is_dir() {
+                    if ops.remove_empty_dir(path).is_ok() {
+                        snapshot.record(ChangeKind::DirRemoved, path, None, &ops)?;
+                    } else {
+                        say!("⚠️  Kept non-empty directory: {}", path.display());
+                    }
+                }
+            }
+            RevertStep::CreateDir { path, .. } => {
+                snapshot.create_dirs(path, &ops)?;
             }
-            fs::copy(entry.path(), &dest)?;
-            println!("🔁 Restored: {}", dest.display());
         }
     }
-    Ok(())
+
+    let source_name = source.file_name().unwrap_or_default().to_string_lossy().to_string();
+    snapshot.finish(BackupMeta {
+        kind: BackupKind::Revert,
+        parent: Some(source_name),
+        created: timestamp,
+        source_archive: source.display().to_string(),
+        hostname: host::hostname(),
+        items: steps.iter().map(|s| s.target().display().to_string()).collect(),
+        ..Default::default()
+    })?;
+
+    Ok(snapshot)
 }
 
-pub fn run_revert_ui() -> io::Result<()> {
-    let backups_dir = PathBuf::from("backups");
-    let mut entries = fs::read_dir(&backups_dir)?
-        .filter_map(Result::ok)
-        .filter(|e| e.path().is_dir())
-        .collect::<Vec<_>>();
+/// Label for the backup list: the name, plus what it undid for revert snapshots
+fn chain_label(name: &str, metas: &HashMap<String, BackupMeta>) -> String {
+    let mut label = name.to_string();
+    let mut current = name.to_string();
+    let mut seen = vec![current.clone()];
 
-    entries.sort_by_key(|e| e.path());
-    entries.reverse(); // newest first
+    while let Some(parent) = metas.get(&current).and_then(|m| m.parent.clone()) {
+        if seen.contains(&parent) {
+            break;
+        }
+        label.push_str(&format!("  ↩ {}", parent));
+        seen.push(parent.clone());
+        current = parent;
+    }
+
+    if let Some(kind) = metas.get(name).map(|m| m.kind) {
+        if kind == BackupKind::Revert {
+            label.push_str("  (pre-revert)");
+        }
+    }
+    label
+}
 
-    if entries.is_empty() {
-        println!("❌ No backups available.");
+pub fn run_revert_ui(target: &TargetPaths, escalation: Escalation, dry_run: bool) -> io::Result<()> {
+    let mut backups = BackupList::load()?;
+    if backups.entries.is_empty() {
+        say!("❌ No backups available.");
         return Ok(());
     }
 
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    let mut terminal = TerminalGuard::enter()?;
 
-    let selected = ui_loop(&mut terminal, &entries)?;
+    let selected = ui_loop(&mut terminal, &mut backups)?;
 
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
+    // 🔍 Drill into the backup to tick individual items or files
+    let chosen = match selected {
+        Some((index, true)) => {
+            let mut picker = RevertPicker::new(plan_revert(&backups.entries[index], target)?);
+            let confirmed = pick_loop(&mut terminal, &mut picker)?;
+            confirmed.then(|| (index, picker.picked()))
+        }
+        Some((index, false)) => Some((index, plan_revert(&backups.entries[index], target)?)),
+        None => None,
+    };
 
-    if let Some(index) = selected {
-        let backup_path = entries[index].path();
-        println!("⚠️  This will overwrite your current configs with backup: {}", backup_path.display());
-        println!("Proceeding...\n");
+    drop(terminal);
 
-        match restore_backup_dir(&backup_path) {
-            Ok(_) => println!("✅ Revert complete."),
-            Err(e) => println!("❌ Error during revert: {}", e),
+    if let Some((index, steps)) = chosen {
+        if let Err(e) = revert_backup(&backups.entries[index], &steps, escalation, dry_run) {
+            say!("{}", e);
         }
     }
 
     Ok(())
 }
 
-fn ui_loop<B: tui::backend::Backend>(
-    terminal: &mut Terminal<B>,
-    entries: &[fs::DirEntry],
-) -> io::Result<Option<usize>> {
-    let mut state = ListState::default();
-    if !entries.is_empty() {
-        state.select(Some(0));
+/// Show the plan for reverting a backup and carry it out once confirmed
+pub fn revert_backup(backup_path: &Path, steps: &[RevertStep], escalation: Escalation, dry_run: bool) -> Result<(), String> {
+    if steps.is_empty() {
+        say!("❌ Nothing selected to revert.");
+        return Ok(());
+    }
+
+    say!("🧭 Revert Plan{}: {}", if dry_run { " (dry-run)" } else { "" }, backup_path.display());
+    say!("───────────────────────────────────────────────");
+    for step in steps {
+        say!("{}", step.describe());
+    }
+
+    if dry_run {
+        say!("\n↪ Current state would be saved to a new backup first");
+        say!("\n🔎 Revert dry-run complete.");
+        return Ok(());
+    }
+
+    // 🛑 Prompt confirmation before continuing
+    say!("\n⚠️  This will overwrite your current configs with backup: {}", backup_path.display());
+    if !console::confirm("Proceed with revert?") {
+        say!("\n❌ Revert cancelled.");
+        return Ok(());
+    }
+
+    let result = apply_revert(steps, escalation, backup_path)
+        .map(|snapshot| {
+            say!("✅ Revert complete.");
+            let mut summary = format!("{} step(s) reverted", steps.len());
+            if snapshot.dir.exists() {
+                say!("📁 Previous state saved to: {}/ (revert it to undo)", snapshot.dir.display());
+                summary.push_str(&format!(", previous state in {}/", snapshot.dir.display()));
+            }
+            summary
+        })
+        .map_err(|e| format!("❌ Error during revert: {}", e));
+    history::record(Operation::Revert, &backup_path.display().to_string(), &result);
+    result.map(|_| ())
+}
+
+/// Most entries shown in the file tree of the details panel
+const MAX_TREE_LINES: usize = 200;
+
+/// Lines for the details panel: `backup.toml` metadata followed by the file tree
+fn backup_details(backup_dir: &Path) -> Vec<String> {
+    let mut lines = vec![];
+
+    match backup::load_meta(backup_dir) {
+        Some(meta) => {
+            lines.push(format!("Created:   {}", meta.created));
+            if let Some(parent) = &meta.parent {
+                lines.push(format!("Undoes:    revert of {}", parent));
+            }
+            lines.push(format!("Host:      {}", meta.hostname));
+            lines.push(format!("Archive:   {}", meta.source_archive));
+            lines.push(format!("Manifest:  {}", meta.manifest_sha256.get(..12).unwrap_or(&meta.manifest_sha256)));
+            lines.push(format!("Files:     {} ({})", meta.file_count, backup::format_size(meta.total_size)));
+            lines.push(format!("Items:     {}", meta.items.join(", ")));
+        }
+        None => lines.push("No backup.toml (created by an older version)".to_string()),
+    }
+
+    lines.push(String::new());
+    lines.push("File tree:".to_string());
+
+    let files_dir = backup_dir.join(backup::FILES_DIR);
+    let tree_root = if files_dir.is_dir() { files_dir } else { backup_dir.to_path_buf() };
+    let mut walker = walkdir::WalkDir::new(&tree_root)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| e.depth() > 1 || (e.file_name() != backup::JOURNAL_FILE && e.file_name() != backup::META_FILE));
+
+    for entry in walker.by_ref().take(MAX_TREE_LINES) {
+        let indent = "  ".repeat(entry.depth());
+        let name = entry.file_name().to_string_lossy();
+        if entry.file_type().is_dir() {
+            lines.push(format!("{}📁 {}/", indent, name));
+        } else {
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            lines.push(format!("{}{} ({})", indent, name, backup::format_size(size)));
+        }
+    }
+    if walker.next().is_some() {
+        lines.push("  …".to_string());
+    }
+
+    lines
+}
+
+/// One row of the partial-revert picker: a whole step, or a single file inside a directory step
+struct PickRow {
+    step: usize,
+    file: Option<RevertStep>,
+    size: u64,
+}
+
+/// Rows for the picker, with directory steps expanded into their files
+fn pick_rows(steps: &[RevertStep]) -> (Vec<ConfigItem>, Vec<PickRow>) {
+    let mut items = vec![];
+    let mut rows = vec![];
+
+    let mut push = |name: String, path: &Path, row: PickRow| {
+        items.push(ConfigItem {
+            name,
+            path: path.to_string_lossy().to_string(),
+            selected: true,
+            policy: None,
+            owner: None,
+            exclude: vec![],
+            tags: vec![],
+            template: false,
+        });
+        rows.push(row);
+    };
+
+    for (idx, step) in steps.iter().enumerate() {
+        push(step.describe(), step.target(), PickRow { step: idx, file: None, size: step.size() });
+
+        // Files below a directory step can be picked one at a time
+        let (dir, system) = match step {
+            RevertStep::Restore { from, system, .. } if from.is_dir() => (from.clone(), *system),
+            RevertStep::Delete { path, system } if path.is_dir() => (path.clone(), *system),
+            _ => continue,
+        };
+        for entry in walkdir::WalkDir::new(&dir)
+            .min_depth(1)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.file_type().is_file())
+        {
+            let sub = entry.path().strip_prefix(&dir).unwrap();
+            let file = match step {
+                RevertStep::Restore { to, .. } => RevertStep::Restore {
+                    from: entry.path().to_path_buf(),
+                    to: to.join(sub),
+                    system,
+                },
+                _ => RevertStep::Delete {
+                    path: entry.path().to_path_buf(),
+                    system,
+                },
+            };
+            let target = file.target().to_path_buf();
+            let size = file.size();
+            push(format!("    └ {}", sub.display()), &target, PickRow { step: idx, file: Some(file), size });
+        }
     }
 
+    (items, rows)
+}
+
+/// Ticked rows that count: steps whose rows are all ticked run whole, otherwise their ticked files run one by one
+fn picked_rows<'a>(rows: &'a [PickRow], items: &[ConfigItem]) -> Vec<&'a PickRow> {
+    let whole = |step: usize| rows.iter().zip(items).all(|(r, i)| r.step != step || i.selected);
+
+    rows.iter()
+        .zip(items)
+        .filter(|(row, item)| item.selected && (row.file.is_none() == whole(row.step)))
+        .map(|(row, _)| row)
+        .collect()
+}
+
+fn picked_steps(steps: &[RevertStep], rows: &[PickRow], items: &[ConfigItem]) -> Vec<RevertStep> {
+    picked_rows(rows, items)
+        .into_iter()
+        .map(|row| row.file.clone().unwrap_or_else(|| steps[row.step].clone()))
+        .collect()
+}
+
+/// Checkbox list of the steps in one backup, directories expanded into their files
+pub struct RevertPicker {
+    steps: Vec<RevertStep>,
+    items: Vec<ConfigItem>,
+    rows: Vec<PickRow>,
+    list: SelectList,
+}
+
+impl RevertPicker {
+    pub fn new(steps: Vec<RevertStep>) -> Self {
+        let (items, rows) = pick_rows(&steps);
+        RevertPicker { steps, items, rows, list: SelectList::default() }
+    }
+
+    /// The steps to run for what is ticked
+    pub fn picked(&self) -> Vec<RevertStep> {
+        picked_steps(&self.steps, &self.rows, &self.items)
+    }
+
+    pub fn draw<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
+        self.list.set_lines(
+            self.items
+                .iter()
+                .map(|item| Line::new(format!("{} ", ui::checkbox(item.selected)), item.name.as_str()))
+                .collect(),
+        );
+        let ticked = self.items.iter().filter(|item| item.selected).count();
+        let size = picked_rows(&self.rows, &self.items).iter().map(|row| row.size).sum();
+        let status = ui::picked_status(ticked, self.items.len(), size);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(3)].as_ref())
+            .split(area);
+
+        self.list.render(f, chunks[0], "🌀 Restitch: Pick What to Revert");
+        ui::help_bar(
+            f,
+            chunks[1],
+            "␣: Toggle  ↵: Revert Ticked  q: Cancel  /: Search  a/n/i: All/None/Invert",
+            &status,
+        );
+    }
+
+    /// Ticking is handled here; `Enter`, `q` and other keys come back
+    pub fn handle(&mut self, event: Event) -> Option<KeyEvent> {
+        let key = self.list.handle(event)?;
+        if let Some(bulk) = Bulk::from_key(key.code) {
+            let visible: Vec<usize> = self.list.visible().collect();
+            for &i in &visible {
+                bulk.apply(&mut self.items[i].selected);
+            }
+            // A step whose files are all hidden by the search takes them along
+            for &i in &visible {
+                let step = self.rows[i].step;
+                let hidden = |j: usize| self.rows[j].step != step || j == i || !visible.contains(&j);
+                if self.rows[i].file.is_none() && (0..self.rows.len()).all(hidden) {
+                    self.tick_step(step, self.items[i].selected);
+                }
+            }
+            self.sync_steps();
+            return None;
+        }
+        if key.code != KeyCode::Char(' ') {
+            return Some(key);
+        }
+        if let Some(i) = self.list.selected() {
+            let selected = !self.items[i].selected;
+            self.items[i].selected = selected;
+            // Toggling a whole step carries its files along
+            if self.rows[i].file.is_none() {
+                self.tick_step(self.rows[i].step, selected);
+            }
+            self.sync_steps();
+        }
+        None
+    }
+
+    fn tick_step(&mut self, step: usize, selected: bool) {
+        for (item, row) in self.items.iter_mut().zip(&self.rows) {
+            if row.step == step {
+                item.selected = selected;
+            }
+        }
+    }
+
+    /// A directory step stays ticked only while every file under it is
+    fn sync_steps(&mut self) {
+        for i in 0..self.rows.len() {
+            if self.rows[i].file.is_some() {
+                continue;
+            }
+            let step = self.rows[i].step;
+            let mut files = self.rows.iter().zip(&self.items).filter(|(row, _)| row.step == step && row.file.is_some());
+            if let Some(first) = files.next() {
+                let all = first.1.selected && files.all(|(_, item)| item.selected);
+                self.items[i].selected = all;
+            }
+        }
+    }
+}
+
+fn pick_loop<B: Backend>(terminal: &mut Terminal<B>, picker: &mut RevertPicker) -> io::Result<bool> {
     loop {
         terminal.draw(|f| {
             let size = f.size();
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([Constraint::Min(1), Constraint::Length(2)].as_ref())
-                .split(size);
+            picker.draw(f, size);
+        })?;
 
-            let list_items: Vec<ListItem> = entries
-                .iter()
-                .enumerate()
-                .map(|(i, entry)| {
-                    let label = entry.file_name().to_string_lossy().to_string();
-                    let style = if state.selected() == Some(i) {
-                        Style::default().add_modifier(Modifier::REVERSED)
-                    } else {
-                        Style::default()
-                    };
-                    ListItem::new(Span::raw(label)).style(style)
-                })
-                .collect();
-
-            let list = List::new(list_items)
-                .block(Block::default().title("🌀 Restitch: Select Backup to Revert").borders(Borders::ALL))
-                .highlight_symbol(">>");
-
-            f.render_stateful_widget(list, chunks[0], &mut state);
-
-            let help = Block::default()
-                .title("↑↓: Navigate  ↵: Revert Selected  q: Cancel")
-                .borders(Borders::ALL);
-            f.render_widget(help, chunks[1]);
+        let Some(key) = ui::next_event()?.and_then(|event| picker.handle(event)) else {
+            continue;
+        };
+        match key.code {
+            KeyCode::Char('q') => return Ok(false),
+            KeyCode::Enter => return Ok(true),
+            _ => {}
+        }
+    }
+}
+
+/// Backups, newest first, with the details of the highlighted one beside them
+#[derive(Default)]
+pub struct BackupList {
+    pub entries: Vec<PathBuf>,
+    labels: Vec<String>,
+    /// Computed once per backup, the first time it is highlighted
+    details: HashMap<usize, Vec<String>>,
+    list: SelectList,
+}
+
+impl BackupList {
+    pub fn load() -> io::Result<Self> {
+        let mut entries = fs::read_dir(backup::BACKUPS_DIR)?
+            .filter_map(Result::ok)
+            .map(|e| e.path())
+            .filter(|path| path.is_dir())
+            .collect::<Vec<_>>();
+
+        entries.sort();
+        entries.reverse(); // newest first
+
+        let name = |path: &Path| path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let metas: HashMap<String, BackupMeta> = entries
+            .iter()
+            .filter_map(|path| Some((name(path), backup::load_meta(path)?)))
+            .collect();
+        let labels = entries.iter().map(|path| chain_label(&name(path), &metas)).collect();
+
+        Ok(BackupList { entries, labels, ..Default::default() })
+    }
+
+    /// The highlighted backup
+    pub fn selected(&self) -> Option<&Path> {
+        self.list.selected().map(|i| self.entries[i].as_path())
+    }
+
+    pub fn draw<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
+        self.list.set_lines(self.labels.iter().map(|label| Line::new("", label.as_str())).collect());
+        if let Some(i) = self.list.selected() {
+            self.details.entry(i).or_insert_with(|| backup_details(&self.entries[i]));
+        }
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(3)].as_ref())
+            .split(area);
+
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(40), Constraint::Percentage(60)].as_ref())
+            .split(chunks[0]);
+
+        self.list.render(f, panes[0], "🌀 Restitch: Select Backup to Revert");
+
+        let text = self
+            .list
+            .selected()
+            .and_then(|i| self.details.get(&i))
+            .map(|lines| lines.join("\n"))
+            .unwrap_or_default();
+        let panel = Paragraph::new(text)
+            .block(Block::default().title("Details").borders(Borders::ALL));
+        f.render_widget(panel, panes[1]);
+
+        let status = format!("{} backup(s)", self.entries.len());
+        ui::help_bar(f, chunks[1], "↑↓: Navigate  /: Search  ↵: Revert Selected  →: Pick Files  q: Cancel", &status);
+    }
+
+    /// Moving and searching are handled here; every other key comes back
+    pub fn handle(&mut self, event: Event) -> Option<KeyEvent> {
+        self.list.handle(event)
+    }
+}
+
+/// Backup list; returns the chosen backup and whether to pick files from it
+fn ui_loop<B: Backend>(terminal: &mut Terminal<B>, backups: &mut BackupList) -> io::Result<Option<(usize, bool)>> {
+    loop {
+        terminal.draw(|f| {
+            let size = f.size();
+            backups.draw(f, size);
         })?;
 
-        if event::poll(std::time::Duration::from_millis(200))? {
-            match event::read()? {
-                Event::Key(key) => match key.code {
-                    KeyCode::Char('q') => return Ok(None),
-                    KeyCode::Enter => return Ok(state.selected()),
-                    KeyCode::Down => {
-                        if let Some(i) = state.selected() {
-                            let next = if i >= entries.len() - 1 { 0 } else { i + 1 };
-                            state.select(Some(next));
-                        }
-                    }
-                    KeyCode::Up => {
-                        if let Some(i) = state.selected() {
-                            let prev = if i == 0 { entries.len() - 1 } else { i - 1 };
-                            state.select(Some(prev));
-                        }
-                    }
-                    _ => {}
-                },
-                _ => {}
-            }
+        let Some(key) = ui::next_event()?.and_then(|event| backups.handle(event)) else {
+            continue;
+        };
+        let selected = backups.list.selected();
+        match key.code {
+            KeyCode::Char('q') => return Ok(None),
+            KeyCode::Enter => return Ok(selected.map(|i| (i, false))),
+            KeyCode::Right | KeyCode::Char('l') => return Ok(selected.map(|i| (i, true))),
+            _ => {}
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unticked_file_under_directory_step_is_kept() {
+        let tmp = tempfile::tempdir().unwrap();
+        let from = tmp.path().join("backup/conf");
+        fs::create_dir_all(&from).unwrap();
+        fs::write(from.join("a"), "a").unwrap();
+        fs::write(from.join("b"), "b").unwrap();
+        let to = tmp.path().join("home/conf");
+        let mut picker = RevertPicker::new(vec![RevertStep::Restore { from, to: to.clone(), system: false }]);
+        assert_eq!(picker.items.len(), 3);
+
+        // Rows: the directory, then `a`, then `b`
+        picker.items[1].selected = false;
+        picker.sync_steps();
+        assert!(!picker.items[0].selected);
+
+        let picked = picker.picked();
+        assert_eq!(picked.len(), 1);
+        assert!(matches!(&picked[0], RevertStep::Restore { to: target, .. } if *target == to.join("b")));
+    }
+
+    #[test]
+    fn fully_ticked_directory_step_runs_whole() {
+        let tmp = tempfile::tempdir().unwrap();
+        let from = tmp.path().join("backup/conf");
+        fs::create_dir_all(&from).unwrap();
+        fs::write(from.join("a"), "a").unwrap();
+        let to = tmp.path().join("home/conf");
+        let picker = RevertPicker::new(vec![RevertStep::Restore { from, to: to.clone(), system: false }]);
+
+        let picked = picker.picked();
+        assert_eq!(picked.len(), 1);
+        assert!(matches!(&picked[0], RevertStep::Restore { to: target, .. } if *target == to));
+    }
+
+    #[test]
+    fn corrupt_journal_fails_instead_of_falling_back() {
+        let tmp = tempfile::tempdir().unwrap();
+        let backup_dir = tmp.path().join("backup");
+        fs::create_dir_all(backup_dir.join(".config")).unwrap();
+        fs::write(backup_dir.join(".config/app.conf"), "old").unwrap();
+        fs::write(backup_dir.join(backup::JOURNAL_FILE), "[[entries]\nkind = ").unwrap();
+        let target = TargetPaths::new(None, Some(tmp.path().join("home")));
+
+        let result = plan_revert(&backup_dir, &target);
+        assert!(matches!(result, Err(e) if e.kind() == io::ErrorKind::InvalidData));
+    }
+
+    #[test]
+    fn backup_without_journal_restores_its_files_but_not_the_sidecars() {
+        let tmp = tempfile::tempdir().unwrap();
+        let backup_dir = tmp.path().join("backup");
+        fs::create_dir_all(backup_dir.join(".config")).unwrap();
+        fs::write(backup_dir.join(".config/app.conf"), "old").unwrap();
+        fs::write(backup_dir.join(backup::META_FILE), "created = \"then\"").unwrap();
+        let home = tmp.path().join("home");
+        let target = TargetPaths::new(None, Some(home.clone()));
+
+        let steps = plan_revert(&backup_dir, &target).unwrap();
+        assert_eq!(steps.len(), 1);
+        assert!(matches!(&steps[0], RevertStep::Restore { to, .. } if *to == home.join(".config/app.conf")));
+    }
+}
@@ -0,0 +1,158 @@
+// safety.rs
+
+use crate::backup;
+use crate::paths;
+use crate::prune;
+
+use serde::Deserialize;
+use std::path::{Component, Path, PathBuf};
+use tar::EntryType;
+
+/// Archives larger than this once unpacked are refused unless the limit is raised
+pub const DEFAULT_MAX_TOTAL_SIZE: u64 = 1 << 30;
+/// Archives with more entries than this are refused unless the limit is raised
+pub const DEFAULT_MAX_ENTRIES: usize = 100_000;
+
+/// `[restore]` settings from the targets file: where an archive may write and how big it may be
+#[derive(Debug, Deserialize, Default, Clone)]
+pub struct RestoreLimits {
+    /// System-level directories outside `$HOME` that archives may restore into
+    #[serde(default)]
+    pub allowed_roots: Vec<String>,
+    /// Largest total unpacked size accepted, e.g. "1G"
+    pub max_total_size: Option<String>,
+    /// Most tar entries accepted
+    pub max_entries: Option<usize>,
+}
+
+/// Validates manifest paths and tar entries before anything is written.
+///
+/// Entries are counted as they are checked, so one guard covers one pass over the archive.
+#[derive(Debug, Clone)]
+pub struct Guard {
+    allowed_roots: Vec<PathBuf>,
+    max_bytes: u64,
+    max_entries: usize,
+    bytes: u64,
+    entries: usize,
+    /// Symlinks seen so far; nothing may be unpacked through them
+    links: Vec<PathBuf>,
+}
+
+impl Guard {
+    pub fn new(limits: &RestoreLimits) -> Result<Self, String> {
+        let max_bytes = match &limits.max_total_size {
+            Some(size) => prune::parse_size(size)?,
+            None => DEFAULT_MAX_TOTAL_SIZE,
+        };
+        Ok(Guard {
+            allowed_roots: limits.allowed_roots.iter().map(|root| paths::expand(root)).collect(),
+            max_bytes,
+            max_entries: limits.max_entries.unwrap_or(DEFAULT_MAX_ENTRIES),
+            bytes: 0,
+            entries: 0,
+            links: vec![],
+        })
+    }
+
+    /// A fresh guard with the same rules, for another pass over the archive
+    pub fn restart(&self) -> Self {
+        Guard {
+            bytes: 0,
+            entries: 0,
+            links: vec![],
+            ..self.clone()
+        }
+    }
+
+    /// Refuse manifest paths that climb out of their root or name system locations not allowed here
+    pub fn check_item(&self, path: &str) -> Result<(), &'static str> {
+        let rel = paths::archive_rel(path);
+        let inside = rel.components().next().is_some()
+            && rel.components().all(|c| matches!(c, Component::Normal(_)));
+        if !inside || Path::new(path).components().any(|c| c == Component::ParentDir) {
+            return Err("path is not inside its root");
+        }
+
+        if paths::is_system(path) {
+            let absolute = Path::new(path);
+            if !self.allowed_roots.iter().any(|root| absolute.starts_with(root)) {
+                return Err("system path outside the allowed roots");
+            }
+        }
+        Ok(())
+    }
+
+    /// Check one tar entry against the limits and the rules for paths, types and symlinks.
+    ///
+    /// `roots` are the archive locations of the manifest items, used to keep symlinks inside their item.
+    pub fn check_entry(
+        &mut self,
+        raw_path: &Path,
+        kind: EntryType,
+        size: u64,
+        link: Option<&Path>,
+        roots: &[PathBuf],
+    ) -> Result<(), String> {
+        self.entries += 1;
+        if self.entries > self.max_entries {
+            return Err(format!("archive has more than {} entries", self.max_entries));
+        }
+        self.bytes = self.bytes.saturating_add(size);
+        if self.bytes > self.max_bytes {
+            return Err(format!("archive unpacks to more than {}", backup::format_size(self.max_bytes)));
+        }
+
+        if !raw_path.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+            return Err(format!("entry '{}' points outside the archive", raw_path.display()));
+        }
+
+        let path = paths::normalize(raw_path);
+        if let Some(through) = self.links.iter().find(|l| path.starts_with(l) && path != **l) {
+            return Err(format!(
+                "entry '{}' would be written through symlink '{}'",
+                path.display(),
+                through.display()
+            ));
+        }
+
+        match kind {
+            EntryType::Regular | EntryType::Continuous | EntryType::Directory | EntryType::XGlobalHeader => Ok(()),
+            EntryType::Symlink => {
+                let target = link.unwrap_or(Path::new(""));
+                let sub = roots
+                    .iter()
+                    .find_map(|root| path.strip_prefix(root).ok())
+                    .unwrap_or(&path);
+                if escapes(sub, target) {
+                    return Err(format!(
+                        "symlink '{}' -> '{}' escapes its item",
+                        path.display(),
+                        target.display()
+                    ));
+                }
+                self.links.push(path);
+                Ok(())
+            }
+            other => Err(format!("entry '{}' has unsupported type {:?}", path.display(), other)),
+        }
+    }
+}
+
+/// Whether a symlink at `sub` (relative to its item) resolves to somewhere outside the item
+fn escapes(sub: &Path, target: &Path) -> bool {
+    // A link that is the item itself sits in the item's parent, so anything it names is outside
+    if sub.as_os_str().is_empty() {
+        return true;
+    }
+    let mut depth = sub.components().count() - 1;
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 => depth -= 1,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return true,
+        }
+    }
+    false
+}
@@ -0,0 +1,256 @@
+// signing.rs
+
+use crate::paths;
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use tar::{Archive, EntryType};
+
+/// Domain separator so a restitch signature cannot be replayed as anything else
+const SIGNATURE_CONTEXT: &str = "restitch-signature-v1";
+
+const SECRET_KEY_FILE: &str = "signing.key";
+const PUBLIC_KEY_FILE: &str = "signing.pub";
+const TRUSTED_KEYS_DIR: &str = "trusted_keys";
+
+/// Detached signature written next to an archive as `<name>.sig`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignatureFile {
+    /// Hex-encoded ed25519 public key of the signer
+    pub key: String,
+    pub manifest_sha256: String,
+    /// Digest of every archive entry, keyed by its path in the archive
+    pub entries: BTreeMap<String, String>,
+    /// Hex-encoded ed25519 signature over all of the above
+    pub signature: String,
+}
+
+/// Outcome of checking an archive's signature
+pub enum Verdict {
+    /// Signed by the trusted key with this name
+    Trusted(String),
+    Unsigned,
+    /// Signed, but not by a trusted key or not matching the archive
+    Untrusted(String),
+}
+
+/// `$XDG_CONFIG_HOME/restitch`, where keys live
+pub fn key_dir() -> PathBuf {
+    paths::expand("$XDG_CONFIG_HOME/restitch")
+}
+
+pub fn signature_path(archive: &Path) -> PathBuf {
+    let name = archive.file_name().unwrap_or_default().to_string_lossy();
+    let stem = name.strip_suffix(".tar.gz").unwrap_or(&name);
+    archive.with_file_name(format!("{stem}.sig"))
+}
+
+/// Short form of a public key for display
+pub fn fingerprint(key: &VerifyingKey) -> String {
+    hex::encode(&key.as_bytes()[..8])
+}
+
+fn parse_public_key(text: &str) -> Result<VerifyingKey, String> {
+    let bytes: [u8; 32] = hex::decode(text.trim())
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| "not a hex-encoded ed25519 public key".to_string())?;
+    VerifyingKey::from_bytes(&bytes).map_err(|e| e.to_string())
+}
+
+/// Create a signing key pair; refuses to replace an existing key unless `force`
+pub fn generate_key(force: bool) -> Result<VerifyingKey, String> {
+    let dir = key_dir();
+    let secret_path = dir.join(SECRET_KEY_FILE);
+    if secret_path.exists() && !force {
+        return Err(format!("a signing key already exists at {} (use --force to replace it)", secret_path.display()));
+    }
+
+    let mut seed = [0u8; 32];
+    File::open("/dev/urandom")
+        .and_then(|mut f| f.read_exact(&mut seed))
+        .map_err(|e| format!("could not read random bytes: {}", e))?;
+    let key = SigningKey::from_bytes(&seed);
+
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    write_secret(&secret_path, &(hex::encode(key.to_bytes()) + "\n")).map_err(|e| e.to_string())?;
+    fs::write(dir.join(PUBLIC_KEY_FILE), hex::encode(key.verifying_key().as_bytes()) + "\n")
+        .map_err(|e| e.to_string())?;
+
+    Ok(key.verifying_key())
+}
+
+/// Write the secret key into a file that is private from the moment it exists
+fn write_secret(path: &Path, text: &str) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let mut file = OpenOptions::new().write(true).create_new(true).mode(0o600).open(path)?;
+    file.write_all(text.as_bytes())
+}
+
+/// Add a public key (a `.pub` file or the hex string itself) to the trusted keys
+pub fn trust_key(key: &str, name: Option<&str>) -> Result<PathBuf, String> {
+    let from_file = Path::new(key).is_file();
+    let text = if from_file {
+        fs::read_to_string(key).map_err(|e| e.to_string())?
+    } else {
+        key.to_string()
+    };
+    let public = parse_public_key(&text)?;
+
+    let name = match name {
+        Some(name) => name.to_string(),
+        None if from_file => Path::new(key)
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string(),
+        None => fingerprint(&public),
+    };
+    if name.is_empty() || name.contains('/') {
+        return Err(format!("invalid key name '{}'", name));
+    }
+
+    let dir = key_dir().join(TRUSTED_KEYS_DIR);
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let path = dir.join(format!("{name}.pub"));
+    fs::write(&path, hex::encode(public.as_bytes()) + "\n").map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+fn load_signing_key() -> Option<SigningKey> {
+    let text = fs::read_to_string(key_dir().join(SECRET_KEY_FILE)).ok()?;
+    let bytes: [u8; 32] = hex::decode(text.trim()).ok()?.try_into().ok()?;
+    Some(SigningKey::from_bytes(&bytes))
+}
+
+/// Trusted public keys by name; our own key is always trusted
+fn trusted_keys() -> Vec<(String, VerifyingKey)> {
+    let mut keys = vec![];
+
+    if let Ok(text) = fs::read_to_string(key_dir().join(PUBLIC_KEY_FILE)) {
+        if let Ok(key) = parse_public_key(&text) {
+            keys.push(("own key".to_string(), key));
+        }
+    }
+
+    if let Ok(entries) = fs::read_dir(key_dir().join(TRUSTED_KEYS_DIR)) {
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "pub") {
+                if let Some(key) = fs::read_to_string(&path).ok().and_then(|t| parse_public_key(&t).ok()) {
+                    keys.push((path.file_stem().unwrap_or_default().to_string_lossy().to_string(), key));
+                }
+            }
+        }
+    }
+    keys
+}
+
+/// Digest of a file's content, as recorded in signatures
+pub fn content_digest(mut reader: impl Read) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut reader, &mut hasher)?;
+    Ok(format!("sha256:{:x}", hasher.finalize()))
+}
+
+/// What gets signed for one entry: content hash for files, the target for symlinks
+pub fn entry_digest<R: Read>(entry: &mut tar::Entry<R>) -> io::Result<String> {
+    Ok(match entry.header().entry_type() {
+        EntryType::Regular | EntryType::Continuous => content_digest(entry)?,
+        EntryType::Directory => "dir".to_string(),
+        EntryType::Symlink => format!(
+            "link:{}",
+            entry.link_name()?.unwrap_or_default().display()
+        ),
+        other => format!("{:?}", other).to_lowercase(),
+    })
+}
+
+/// Digest of every entry in an archive
+pub fn archive_digests(archive: &Path) -> io::Result<BTreeMap<String, String>> {
+    let mut digests = BTreeMap::new();
+    for entry in Archive::new(GzDecoder::new(File::open(archive)?)).entries()? {
+        let mut entry = entry?;
+        let path = paths::normalize(&entry.path()?);
+        digests.insert(path.to_string_lossy().to_string(), entry_digest(&mut entry)?);
+    }
+    Ok(digests)
+}
+
+fn message(manifest_sha256: &str, entries: &BTreeMap<String, String>) -> Vec<u8> {
+    let mut msg = format!("{SIGNATURE_CONTEXT}\nmanifest {manifest_sha256}\n");
+    // Quoted and escaped, so no path or link target can forge extra lines
+    for (path, digest) in entries {
+        msg.push_str(&format!("entry {digest:?} {path:?}\n"));
+    }
+    msg.into_bytes()
+}
+
+/// Sign a freshly exported archive if a signing key exists; returns the key used
+pub fn sign_archive(archive: &Path, manifest: &Path) -> Result<Option<VerifyingKey>, String> {
+    let Some(key) = load_signing_key() else {
+        return Ok(None);
+    };
+
+    let manifest_sha256 = format!("{:x}", Sha256::digest(fs::read(manifest).map_err(|e| e.to_string())?));
+    let entries = archive_digests(archive).map_err(|e| e.to_string())?;
+    let signature = key.sign(&message(&manifest_sha256, &entries));
+
+    let file = SignatureFile {
+        key: hex::encode(key.verifying_key().as_bytes()),
+        manifest_sha256,
+        entries,
+        signature: hex::encode(signature.to_bytes()),
+    };
+    let toml_string = toml::to_string_pretty(&file).map_err(|e| e.to_string())?;
+    fs::write(signature_path(archive), toml_string).map_err(|e| e.to_string())?;
+
+    Ok(Some(key.verifying_key()))
+}
+
+/// Check an archive against its `.sig`, the manifest being restored and the trusted keys.
+///
+/// `entries` are the digests of the archive as actually read during the restore.
+pub fn verify(archive: &Path, manifest_sha256: &str, entries: &BTreeMap<String, String>) -> Verdict {
+    let Ok(text) = fs::read_to_string(signature_path(archive)) else {
+        return Verdict::Unsigned;
+    };
+    let file: SignatureFile = match toml::from_str(&text) {
+        Ok(file) => file,
+        Err(_) => return Verdict::Untrusted("signature file is malformed".to_string()),
+    };
+
+    let Ok(key) = parse_public_key(&file.key) else {
+        return Verdict::Untrusted("signature names an invalid key".to_string());
+    };
+    let Some((name, _)) = trusted_keys().into_iter().find(|(_, trusted)| *trusted == key) else {
+        return Verdict::Untrusted(format!("signed by untrusted key {}", fingerprint(&key)));
+    };
+
+    let signature = match hex::decode(&file.signature).ok().and_then(|b| Signature::from_slice(&b).ok()) {
+        Some(signature) => signature,
+        None => return Verdict::Untrusted("signature is malformed".to_string()),
+    };
+    if key.verify_strict(&message(&file.manifest_sha256, &file.entries), &signature).is_err() {
+        return Verdict::Untrusted("signature does not match".to_string());
+    }
+
+    if file.manifest_sha256 != manifest_sha256 {
+        return Verdict::Untrusted("manifest was changed after signing".to_string());
+    }
+    if &file.entries != entries {
+        return Verdict::Untrusted("archive contents were changed after signing".to_string());
+    }
+
+    Verdict::Trusted(name)
+}
@@ -0,0 +1,193 @@
+// template.rs
+
+use crate::console;
+use crate::host;
+use crate::paths;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Template variables by name
+pub type Vars = BTreeMap<String, String>;
+
+/// `$XDG_CONFIG_HOME/restitch/vars.toml`: this machine's values, as `name = "value"` pairs
+pub fn vars_path() -> PathBuf {
+    paths::expand("$XDG_CONFIG_HOME/restitch/vars.toml")
+}
+
+/// Variables from the host vars file; a missing file means none
+pub fn load_host_vars() -> Result<Vars, String> {
+    let path = vars_path();
+    match fs::read_to_string(&path) {
+        Ok(content) => toml::from_str(&content).map_err(|e| format!("❌ Invalid vars file '{}': {}", path.display(), e)),
+        Err(_) => Ok(Vars::new()),
+    }
+}
+
+/// Values every machine knows without being told: `hostname`, `user` and `home`
+pub fn builtin_vars() -> Vars {
+    let mut vars = Vars::new();
+    vars.insert("hostname".to_string(), host::hostname());
+    if let Ok(user) = env::var("USER") {
+        vars.insert("user".to_string(), user);
+    }
+    if let Some(home) = dirs::home_dir() {
+        vars.insert("home".to_string(), home.display().to_string());
+    }
+    vars
+}
+
+/// The built-ins, overridden by whatever the host vars file sets
+pub fn machine_vars(host_vars: Vars) -> Vars {
+    let mut vars = builtin_vars();
+    vars.extend(host_vars);
+    vars
+}
+
+/// Parse a `--var KEY=VALUE` flag
+pub fn parse_var(spec: &str) -> Result<(String, String), String> {
+    match spec.split_once('=') {
+        Some((name, value)) if is_name(name) => Ok((name.to_string(), value.to_string())),
+        _ => Err("expected KEY=VALUE with a KEY of letters, digits, '_', '-' or '.'".to_string()),
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+/// Pieces of a template: literal text, or a `{{ name }}` placeholder
+enum Piece<'a> {
+    Text(&'a str),
+    Var(&'a str),
+}
+
+fn pieces(text: &str) -> Vec<Piece<'_>> {
+    let mut out = vec![];
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            break;
+        };
+        let name = after[..end].trim();
+        if is_name(name) {
+            out.push(Piece::Text(&rest[..start]));
+            out.push(Piece::Var(name));
+        } else {
+            // Not a placeholder; keep the braces as text and look further on
+            out.push(Piece::Text(&rest[..start + 2]));
+            rest = after;
+            continue;
+        }
+        rest = &after[end + 2..];
+    }
+    out.push(Piece::Text(rest));
+    out
+}
+
+/// Placeholder names used in `text`, each once
+pub fn placeholders(text: &str) -> Vec<String> {
+    let mut names: Vec<String> = vec![];
+    for piece in pieces(text) {
+        if let Piece::Var(name) = piece {
+            if !names.iter().any(|n| n == name) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names
+}
+
+/// Fill in placeholders; ones without a value are left as they are
+pub fn render(text: &str, vars: &Vars) -> String {
+    let mut out = String::with_capacity(text.len());
+    for piece in pieces(text) {
+        match piece {
+            Piece::Text(t) => out.push_str(t),
+            Piece::Var(name) => match vars.get(name) {
+                Some(value) => out.push_str(value),
+                None => out.push_str(&format!("{{{{ {} }}}}", name)),
+            },
+        }
+    }
+    out
+}
+
+/// Values shorter than this are too likely to occur by chance to be turned into placeholders
+const MIN_VALUE_LEN: usize = 3;
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Turn this machine's values back into placeholders, longest value first.
+///
+/// Only whole words match: `ali` is never found inside `alias`.
+pub fn templatize(text: &str, vars: &Vars) -> String {
+    let mut by_length: Vec<(&String, &String)> =
+        vars.iter().filter(|(_, v)| v.chars().count() >= MIN_VALUE_LEN).collect();
+    by_length.sort_by_key(|(_, value)| std::cmp::Reverse(value.len()));
+
+    // One pass, so a value is never matched inside a placeholder written earlier
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut prev: Option<char> = None;
+    while let Some(c) = rest.chars().next() {
+        let whole = |value: &str| {
+            let after = rest[value.len()..].chars().next();
+            let starts_word = value.chars().next().is_some_and(is_word);
+            let ends_word = value.chars().last().is_some_and(is_word);
+            let joined_before = starts_word && prev.is_some_and(is_word);
+            let joined_after = ends_word && after.is_some_and(is_word);
+            !joined_before && !joined_after
+        };
+        match by_length.iter().find(|(_, value)| rest.starts_with(value.as_str()) && whole(value)) {
+            Some((name, value)) => {
+                out.push_str(&format!("{{{{ {} }}}}", name));
+                rest = &rest[value.len()..];
+                prev = value.chars().last();
+            }
+            None => {
+                out.push(c);
+                rest = &rest[c.len_utf8()..];
+                prev = Some(c);
+            }
+        }
+    }
+    out
+}
+
+/// Ask for each variable that has no value yet
+pub fn prompt_missing(names: &[String], vars: &mut Vars) {
+    for name in names {
+        vars.insert(name.clone(), console::ask(&format!("🧩 Value for '{}': ", name)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vars {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn templatize_then_render_round_trips() {
+        let vars = vars(&[("email", "me@example.com"), ("home", "/home/alice")]);
+        let text = "email = me@example.com\nsource /home/alice/.aliases\n";
+        let templated = templatize(text, &vars);
+        assert_eq!(templated, "email = {{ email }}\nsource {{ home }}/.aliases\n");
+        assert_eq!(render(&templated, &vars), text);
+    }
+
+    #[test]
+    fn values_inside_other_words_are_left_alone() {
+        let vars = vars(&[("user", "ali"), ("monitor", "1"), ("host", "box")]);
+        let text = "alias ll='ls -l'\nuser=ali\nscale = 1\nsandbox /home/alice\n";
+        assert_eq!(templatize(text, &vars), "alias ll='ls -l'\nuser={{ user }}\nscale = 1\nsandbox /home/alice\n");
+    }
+}
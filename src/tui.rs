@@ -1,132 +1,590 @@
-use crate::config::ConfigItem;
-use crate::detectors::scan_targets_from_file;
-
-use std::io;
-use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-};
+use crate::check;
+use crate::config::{ConfigItem, Selection};
+use crate::detectors::{scan_targets_from_file, Skipped};
+use crate::editor::TargetsFile;
+use crate::paths;
+use crate::backup;
+use crate::details::{DetailWorker, Details};
+use crate::ui::{self, Bulk, Line, SelectList};
+
+use std::fs;
+use std::path::Path;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 use tui::{
-    backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
-    style::{Modifier, Style},
-    text::Span,
-    widgets::{Block, Borders, List, ListItem, ListState},
-    Terminal,
+    backend::Backend,
+    layout::{Constraint, Direction, Layout, Rect},
+    text::Spans,
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
 };
 
-/// Safely runs the TUI interface with proper terminal cleanup
-pub fn run_ui_with_cleanup(config_path: &str) -> io::Result<Vec<ConfigItem>> {
-    // Load config items before modifying the terminal
-    let mut items = match scan_targets_from_file(config_path) {
-        Ok(i) => i,
-        Err(msg) => {
-            eprintln!("{msg}");
-            eprintln!("💡 Example format:\n\n  [[config]]\n  name = \"Zsh Config\"\n  path = \"~/.zshrc\"\n");
-            return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+/// Entries sharing their first tag, shown as one collapsible row
+struct Group {
+    name: String,
+    members: Vec<usize>,
+    expanded: bool,
+}
+
+/// A visible line in the list
+#[derive(Clone, Copy, PartialEq)]
+enum Row {
+    Group(usize),
+    Item(usize),
+}
+
+/// Groups by first tag, in the order the tags first appear; no tags anywhere means a flat list
+fn group_items(items: &[ConfigItem]) -> Vec<Group> {
+    if items.iter().all(|item| item.tags.is_empty()) {
+        return vec![];
+    }
+
+    let mut groups: Vec<Group> = vec![];
+    let mut untagged = vec![];
+    for (idx, item) in items.iter().enumerate() {
+        let Some(tag) = item.tags.first() else {
+            untagged.push(idx);
+            continue;
+        };
+        match groups.iter_mut().find(|g| &g.name == tag) {
+            Some(group) => group.members.push(idx),
+            None => groups.push(Group { name: tag.clone(), members: vec![idx], expanded: false }),
         }
-    };
+    }
+    if !untagged.is_empty() {
+        groups.push(Group { name: "untagged".to_string(), members: untagged, expanded: false });
+    }
+    groups
+}
+
+fn visible_rows(groups: &[Group], item_count: usize) -> Vec<Row> {
+    if groups.is_empty() {
+        return (0..item_count).map(Row::Item).collect();
+    }
+    let mut rows = vec![];
+    for (g, group) in groups.iter().enumerate() {
+        rows.push(Row::Group(g));
+        if group.expanded {
+            rows.extend(group.members.iter().map(|&idx| Row::Item(idx)));
+        }
+    }
+    rows
+}
+
+/// Path-completing file browser for adding a target
+struct Browser {
+    /// Path as typed, e.g. `~/.config/ki`
+    input: String,
+    /// Entries of the input's directory that start with its last component; directories end in `/`
+    matches: Vec<String>,
+    list: SelectList,
+}
+
+impl Browser {
+    fn new() -> Self {
+        let mut browser = Browser { input: "~/".to_string(), matches: vec![], list: SelectList::default() };
+        browser.refresh();
+        browser
+    }
+
+    fn split(&self) -> (&str, &str) {
+        match self.input.rfind('/') {
+            Some(i) => self.input.split_at(i + 1),
+            None => ("", self.input.as_str()),
+        }
+    }
 
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    fn refresh(&mut self) {
+        let (dir, prefix) = self.split();
+        let dir = paths::expand_vars(dir).map(|d| paths::expand(&d)).ok();
+        let mut matches: Vec<String> = dir
+            .and_then(|dir| fs::read_dir(dir).ok())
+            .map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .filter_map(|e| {
+                        let name = e.file_name().to_string_lossy().to_string();
+                        let is_dir = e.path().is_dir();
+                        name.starts_with(prefix).then(|| if is_dir { format!("{name}/") } else { name })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        matches.sort();
+        self.list = SelectList::default();
+        self.list.set_lines(matches.iter().map(|m| Line::new("", m.as_str())).collect());
+        self.matches = matches;
+    }
+
+    /// Replace the last component with the highlighted match
+    fn complete(&mut self) {
+        let Some(choice) = self.list.selected().and_then(|i| self.matches.get(i)).cloned() else {
+            return;
+        };
+        let dir = self.split().0.to_string();
+        self.input = dir + &choice;
+        self.refresh();
+    }
 
-    let result = ui_loop(&mut terminal, &mut items);
+    /// The typed path as it goes in the targets file, if it exists
+    fn chosen(&self) -> Option<String> {
+        let typed = self.input.trim_end_matches('/');
+        let expanded = paths::expand_vars(typed).ok()?;
+        if !paths::expand(&expanded).exists() {
+            return None;
+        }
+        // `~` and variables are kept as typed; anything else is made portable
+        Some(if typed.starts_with(['~', '$']) { typed.to_string() } else { paths::portable(typed) })
+    }
+}
 
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
+/// What the keyboard is driving
+enum Mode {
+    List,
+    AddPath(Browser),
+    AddName { path: String, input: String },
+    Rename { idx: usize, input: String },
+    Tags { idx: usize, input: String },
+    ConfirmDelete(usize),
+}
 
-    result
+/// Apply one edit to the targets file and save it
+fn edit_targets(config_path: &str, edit: impl FnOnce(&mut TargetsFile) -> Result<(), String>) -> Result<(), String> {
+    let mut file = TargetsFile::open(Path::new(config_path))?;
+    edit(&mut file)?;
+    file.save().map_err(|e| format!("could not save '{}': {}", config_path, e))
 }
 
-/// Main interactive TUI loop
-fn ui_loop<B: tui::backend::Backend>(
-    terminal: &mut Terminal<B>,
-    items: &mut Vec<ConfigItem>,
-) -> io::Result<Vec<ConfigItem>> {
-    let mut state = ListState::default();
-    if !items.is_empty() {
-        state.select(Some(0));
+/// Regroup after an edit, keeping groups open that were open before
+fn regroup(items: &[ConfigItem], old: &[Group]) -> Vec<Group> {
+    let mut groups = group_items(items);
+    for group in &mut groups {
+        group.expanded = old.iter().any(|o| o.name == group.name && o.expanded);
     }
+    groups
+}
 
-    loop {
-        terminal.draw(|f| {
-            let size = f.size();
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([Constraint::Min(1), Constraint::Length(2)].as_ref())
-                .split(size);
-
-            let list_items: Vec<ListItem> = items
-                .iter()
-                .enumerate()
-                .map(|(i, item)| {
-                    let prefix = if item.selected { "[x]" } else { "[ ]" };
-                    let line = format!("{} {}", prefix, item.name);
-                    let style = if state.selected() == Some(i) {
-                        Style::default().add_modifier(Modifier::REVERSED)
-                    } else {
-                        Style::default()
+/// Text of the detail pane for a row, and the highlighted preview of its first text file
+fn detail_pane(
+    items: &[ConfigItem],
+    groups: &[Group],
+    row: Option<Row>,
+    worker: &mut DetailWorker,
+) -> (Vec<Spans<'static>>, Option<(String, Vec<Spans<'static>>)>) {
+    let mut lines: Vec<String> = vec![];
+    let size_of = |details: Option<&Details>| {
+        details
+            .map(|d| format!("{} in {} file(s)", backup::format_size(d.size), d.files))
+            .unwrap_or_else(|| "⏳ counting…".to_string())
+    };
+
+    let preview = match row {
+        None => None,
+        Some(Row::Group(g)) => {
+            let group = &groups[g];
+            lines.push(format!("Group:    {}", group.name));
+            lines.push(format!("Entries:  {}", group.members.len()));
+            lines.push(String::new());
+            for &idx in &group.members {
+                lines.push(format!("{}  {}", items[idx].name, size_of(worker.get(&items[idx]))));
+            }
+            None
+        }
+        Some(Row::Item(idx)) => {
+            let item = &items[idx];
+            let details = worker.get(item);
+            lines.push(format!("Path:     {}", item.path));
+            lines.push(format!("Size:     {}", size_of(details)));
+            if let Some(details) = details {
+                lines.push(format!("Modified: {}", details.modified_label()));
+            }
+            if !item.tags.is_empty() {
+                lines.push(format!("Tags:     {}", item.tags.join(", ")));
+            }
+            if !item.exclude.is_empty() {
+                lines.push(format!("Excludes: {}", item.exclude.join(", ")));
+            }
+            if item.template {
+                lines.push("Template: yes".to_string());
+            }
+            if let Some(details) = details {
+                lines.push(String::new());
+                lines.extend(details.tree.iter().cloned());
+            }
+            details.and_then(|d| d.preview.clone())
+        }
+    };
+    (lines.into_iter().map(Spans::from).collect(), preview)
+}
+
+/// The export screen: targets grouped by tag, with the details of the highlighted one beside them
+pub struct ExportScreen {
+    config_path: String,
+    items: Vec<ConfigItem>,
+    /// Targets left out on this machine
+    skipped: Vec<Skipped>,
+    groups: Vec<Group>,
+    list: SelectList,
+    mode: Mode,
+    status: String,
+    worker: DetailWorker,
+    /// Rows as last drawn, so keys act on what is on screen
+    rows: Vec<Row>,
+}
+
+impl ExportScreen {
+    /// Check and load the targets file before the terminal is taken over; problems are printed as well
+    pub fn load(config_path: &str, selection: &Selection) -> Result<Self, String> {
+        // 🩺 Refuse to export from a targets file with errors; warnings stay visible after the TUI closes
+        if Path::new(config_path).exists() {
+            let report = check::check(Path::new(config_path));
+            report.print();
+            if report.errors() > 0 {
+                println!("💡 Fix the errors above, then run `restitch check` to confirm.");
+                return Err(format!("'{}' has {} error(s)", config_path, report.errors()));
+            }
+        }
+
+        let (mut items, skipped) = match scan_targets_from_file(config_path) {
+            Ok(scan) => scan,
+            Err(msg) => {
+                eprintln!("{msg}");
+                eprintln!("💡 Example format:\n\n  [[config]]\n  name = \"Zsh Config\"\n  path = \"~/.zshrc\"\n");
+                return Err(msg);
+            }
+        };
+        // 🏷️ --profile / --tag decide what starts out selected
+        for item in &mut items {
+            item.selected = selection.matches(item);
+        }
+
+        Ok(ExportScreen {
+            config_path: config_path.to_string(),
+            groups: group_items(&items),
+            items,
+            skipped,
+            list: SelectList::default(),
+            mode: Mode::List,
+            status: String::new(),
+            worker: DetailWorker::spawn(),
+            rows: vec![],
+        })
+    }
+
+    /// List the targets left out on this machine, once the terminal is back to normal
+    pub fn print_skipped(&self) {
+        if !self.skipped.is_empty() {
+            println!("\n⏭️  Skipped {} target(s) on this machine:", self.skipped.len());
+            for (i, entry) in self.skipped.iter().enumerate() {
+                let branch = if i + 1 == self.skipped.len() { "└─" } else { "├─" };
+                println!("  {} {}: {}", branch, entry.name, entry.reason);
+            }
+        }
+    }
+
+    /// The ticked targets
+    pub fn picked(&self) -> Vec<ConfigItem> {
+        self.items.iter().filter(|i| i.selected).cloned().collect()
+    }
+
+    pub fn draw<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
+        let ExportScreen { config_path, items, groups, list, mode, status, worker, rows, .. } = self;
+
+        // 🔍 A search looks through every item, collapsed groups or not
+        *rows = if list.is_filtered() {
+            (0..items.len()).map(Row::Item).collect()
+        } else {
+            visible_rows(groups, items.len())
+        };
+        let flat = groups.is_empty() || list.is_filtered();
+        let lines = rows
+            .iter()
+            .map(|row| match *row {
+                Row::Group(g) => {
+                    let group = &groups[g];
+                    let picked = group.members.iter().filter(|&&idx| items[idx].selected).count();
+                    let mark = match picked {
+                        0 => "[ ]",
+                        n if n == group.members.len() => "[x]",
+                        _ => "[-]",
                     };
-                    ListItem::new(Span::raw(line)).style(style)
-                })
-                .collect();
-
-            let list = List::new(list_items)
-                .block(Block::default().title("🌀 Restitch: Select Configs").borders(Borders::ALL))
-                .highlight_symbol(">>");
-
-            f.render_stateful_widget(list, chunks[0], &mut state);
-
-            let help = Block::default()
-                .title("↑↓: Navigate  ␣: Toggle  p: Package  q: Quit")
-                .borders(Borders::ALL);
-            f.render_widget(help, chunks[1]);
-        })?;
-
-        if event::poll(std::time::Duration::from_millis(200))? {
-            match event::read()? {
-                Event::Key(key) => match key.code {
-                    KeyCode::Char('q') => break,
-                    KeyCode::Char('p') => {
-                        let selected_items = items
-                            .iter()
-                            .filter(|i| i.selected)
-                            .cloned()
-                            .collect::<Vec<_>>();
-                        return Ok(selected_items);
+                    let arrow = if group.expanded { "▾" } else { "▸" };
+                    Line::new(format!("{} {} ", arrow, mark), format!("{} ({}/{})", group.name, picked, group.members.len()))
+                }
+                Row::Item(idx) => {
+                    let indent = if flat { "" } else { "    " };
+                    Line::new(format!("{}{} ", indent, ui::checkbox(items[idx].selected)), items[idx].name.as_str())
+                }
+            })
+            .collect();
+        list.set_lines(lines);
+
+        let picked: Vec<&ConfigItem> = items.iter().filter(|item| item.selected).collect();
+        let sizes: Vec<Option<u64>> = picked.iter().map(|item| worker.get(item).map(|d| d.size)).collect();
+        let mut summary = ui::picked_status(picked.len(), items.len(), sizes.iter().flatten().sum());
+        if sizes.contains(&None) {
+            summary.push_str(" (counting…)");
+        }
+        // Asked for last, so the worker gets to the highlighted row first
+        let current = list.selected().and_then(|i| rows.get(i).copied());
+        let (info, preview) = detail_pane(items, groups, current, worker);
+
+        let help = if groups.is_empty() {
+            "␣: Toggle  p: Package  q: Quit  /: Search  a/n/i: All/None/Invert  +: Add  r: Rename  t: Tag  d: Delete  ^R: Refresh"
+        } else {
+            "␣: Toggle  →/←: Expand/Collapse  p: Package  q: Quit  /: Search  a/n/i: All/None/Invert  +: Add  r: Rename  t: Tag  d: Delete  ^R: Refresh"
+        };
+        let bottom = match &*mode {
+            Mode::AddName { path, input } => format!("Name for {}: {}▏", path, input),
+            Mode::Rename { input, .. } => format!("New name: {}▏", input),
+            Mode::Tags { input, .. } => format!("Tags (comma-separated): {}▏", input),
+            Mode::ConfirmDelete(idx) => format!("Delete '{}' from {}? (y/n)", items[*idx].name, config_path),
+            Mode::List | Mode::AddPath(_) => String::new(),
+        };
+        // An edit that failed keeps its prompt open, with the reason beside it
+        let bottom = [summary, bottom, status.clone()].join("   ").trim().to_string();
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(3)].as_ref())
+            .split(area);
+
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(45), Constraint::Percentage(55)].as_ref())
+            .split(chunks[0]);
+        list.render(f, panes[0], "🌀 Restitch: Select Configs");
+
+        // 📋 Details of the highlighted row, with a preview below when there is a text file
+        let details = Block::default().title("📋 Details").borders(Borders::ALL);
+        match preview {
+            Some((name, text)) => {
+                let parts = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Percentage(45), Constraint::Percentage(55)].as_ref())
+                    .split(panes[1]);
+                f.render_widget(Paragraph::new(info).block(details), parts[0]);
+                let title = format!("👁️  {}", name);
+                let preview = Paragraph::new(text).block(Block::default().title(title).borders(Borders::ALL));
+                f.render_widget(preview, parts[1]);
+            }
+            None => f.render_widget(Paragraph::new(info).block(details), panes[1]),
+        }
+        ui::help_bar(f, chunks[1], help, &bottom);
+
+        // ➕ File browser popup over the list
+        if let Mode::AddPath(browser) = mode {
+            let area = ui::centered(area, 70, 60);
+            let parts = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(3), Constraint::Min(1)].as_ref())
+                .split(area);
+            f.render_widget(Clear, area);
+
+            let input = Paragraph::new(format!("{}▏", browser.input)).block(
+                Block::default()
+                    .title("➕ Add target  Tab: Complete  Enter: Add  Esc: Cancel")
+                    .borders(Borders::ALL),
+            );
+            f.render_widget(input, parts[0]);
+
+            browser.list.render(f, parts[1], "");
+        }
+    }
+
+    /// Selecting, grouping and editing are handled here; `p`, `q` and other unused keys come back
+    pub fn handle(&mut self, event: Event) -> Option<KeyEvent> {
+        // Prompts and the file browser take plain keys; the mouse only works on the list
+        let key = match (&self.mode, event) {
+            (Mode::List, event) => self.list.handle(event)?,
+            (_, Event::Key(key)) => key,
+            _ => return None,
+        };
+        let ExportScreen { config_path, items, groups, list, mode, status, worker, rows, .. } = self;
+        let config_path = config_path.as_str();
+        let current = list.selected().and_then(|i| rows.get(i).copied());
+
+        match mode {
+            Mode::List => {}
+            Mode::AddPath(browser) => {
+                match key.code {
+                    KeyCode::Esc => *mode = Mode::List,
+                    KeyCode::Tab => browser.complete(),
+                    KeyCode::Up | KeyCode::Down => {
+                        browser.list.handle(Event::Key(key));
                     }
-                    KeyCode::Down => {
-                        if let Some(i) = state.selected() {
-                            let next = if i >= items.len() - 1 { 0 } else { i + 1 };
-                            state.select(Some(next));
-                        }
+                    KeyCode::Backspace => {
+                        browser.input.pop();
+                        browser.refresh();
+                    }
+                    KeyCode::Char(c) => {
+                        browser.input.push(c);
+                        browser.refresh();
                     }
-                    KeyCode::Up => {
-                        if let Some(i) = state.selected() {
-                            let prev = if i == 0 { items.len() - 1 } else { i - 1 };
-                            state.select(Some(prev));
+                    KeyCode::Enter => match browser.chosen() {
+                        Some(path) => {
+                            let input = Path::new(browser.input.trim_end_matches('/'))
+                                .file_name()
+                                .map(|n| n.to_string_lossy().trim_start_matches('.').to_string())
+                                .unwrap_or_default();
+                            *mode = Mode::AddName { path, input };
                         }
+                        None => browser.complete(),
+                    },
+                    _ => {}
+                }
+                return None;
+            }
+            Mode::AddName { input, .. } | Mode::Rename { input, .. } | Mode::Tags { input, .. } => {
+                match key.code {
+                    KeyCode::Esc => *mode = Mode::List,
+                    KeyCode::Backspace => {
+                        input.pop();
                     }
-                    KeyCode::Char(' ') => {
-                        if let Some(i) = state.selected() {
-                            items[i].selected = !items[i].selected;
+                    KeyCode::Char(c) => input.push(c),
+                    KeyCode::Enter => {
+                        let input = input.trim().to_string();
+                        let edited = match &*mode {
+                            Mode::AddName { path, .. } => path.clone(),
+                            Mode::Rename { idx, .. } | Mode::Tags { idx, .. } => items[*idx].path.clone(),
+                            _ => unreachable!(),
+                        };
+                        let result = match &*mode {
+                            Mode::AddName { .. } | Mode::Rename { .. } if input.is_empty() => {
+                                Err("the name cannot be empty".to_string())
+                            }
+                            Mode::AddName { path, .. } => {
+                                let path = path.clone();
+                                edit_targets(config_path, |file| file.add(&input, &path)).map(|()| {
+                                    items.push(ConfigItem {
+                                        name: input.clone(),
+                                        path,
+                                        selected: true,
+                                        policy: None,
+                                        owner: None,
+                                        exclude: vec![],
+                                        tags: vec![],
+                                        template: false,
+                                    });
+                                    format!("➕ Added '{}'", input)
+                                })
+                            }
+                            Mode::Rename { idx, .. } => {
+                                let idx = *idx;
+                                let old = items[idx].name.clone();
+                                edit_targets(config_path, |file| file.rename(&old, &input)).map(|()| {
+                                    items[idx].name = input.clone();
+                                    format!("✏️  Renamed '{}' to '{}'", old, input)
+                                })
+                            }
+                            Mode::Tags { idx, .. } => {
+                                let idx = *idx;
+                                let tags: Vec<String> = input
+                                    .split([',', ' '])
+                                    .filter(|t| !t.is_empty())
+                                    .map(str::to_string)
+                                    .collect();
+                                let name = items[idx].name.clone();
+                                edit_targets(config_path, |file| file.set_tags(&name, &tags)).map(|()| {
+                                    items[idx].tags = tags;
+                                    format!("🏷️  Tagged '{}'", name)
+                                })
+                            }
+                            _ => unreachable!(),
+                        };
+                        match result {
+                            Ok(message) => {
+                                worker.forget(&edited);
+                                *status = format!("{}, saved to {}", message, config_path);
+                                *groups = regroup(items, groups);
+                                *mode = Mode::List;
+                            }
+                            Err(e) => *status = format!("❌ {}", e),
                         }
                     }
                     _ => {}
-                },
-                _ => {}
+                }
+                return None;
+            }
+            Mode::ConfirmDelete(idx) => {
+                let idx = *idx;
+                if key.code == KeyCode::Char('y') {
+                    let name = items[idx].name.clone();
+                    match edit_targets(config_path, |file| file.remove(&name)) {
+                        Ok(()) => {
+                            worker.forget(&items[idx].path);
+                            items.remove(idx);
+                            *groups = regroup(items, groups);
+                            *status = format!("🗑️  Deleted '{}', saved to {}", name, config_path);
+                        }
+                        Err(e) => *status = format!("❌ {}", e),
+                    }
+                }
+                *mode = Mode::List;
+                return None;
             }
         }
-    }
 
-    Ok(vec![]) // fallback on quit
+        status.clear();
+        if let Some(bulk) = Bulk::from_key(key.code) {
+            // Everything, or only what a search found
+            let targets: Vec<usize> = if list.is_filtered() {
+                list.visible().collect()
+            } else {
+                (0..items.len()).collect()
+            };
+            for idx in targets {
+                bulk.apply(&mut items[idx].selected);
+            }
+            return None;
+        }
+        match key.code {
+            KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                worker.refresh();
+                *status = "🔄 Refreshing details".to_string();
+            }
+            KeyCode::Char(' ') => match current {
+                Some(Row::Item(idx)) => items[idx].selected = !items[idx].selected,
+                // A fully selected group is cleared, anything else selects the whole group
+                Some(Row::Group(g)) => {
+                    let all = groups[g].members.iter().all(|&idx| items[idx].selected);
+                    for &idx in &groups[g].members {
+                        items[idx].selected = !all;
+                    }
+                }
+                None => {}
+            },
+            KeyCode::Right | KeyCode::Enter | KeyCode::Char('l') => {
+                if let Some(Row::Group(g)) = current {
+                    groups[g].expanded = true;
+                }
+            }
+            KeyCode::Left | KeyCode::Char('h') => {
+                let group = match current {
+                    Some(Row::Group(g)) => Some(g),
+                    Some(Row::Item(idx)) => groups.iter().position(|g| g.members.contains(&idx)),
+                    None => None,
+                };
+                if let Some(g) = group {
+                    groups[g].expanded = false;
+                    let rows = visible_rows(groups, items.len());
+                    list.select(rows.iter().position(|row| *row == Row::Group(g)));
+                }
+            }
+            // ✏️ Editing the targets file
+            KeyCode::Char('+') => *mode = Mode::AddPath(Browser::new()),
+            KeyCode::Char('r') | KeyCode::Char('t') | KeyCode::Char('d') => {
+                let Some(Row::Item(idx)) = current else {
+                    *status = "Select an entry first".to_string();
+                    return None;
+                };
+                *mode = match key.code {
+                    KeyCode::Char('r') => Mode::Rename { idx, input: items[idx].name.clone() },
+                    KeyCode::Char('t') => Mode::Tags { idx, input: items[idx].tags.join(", ") },
+                    _ => Mode::ConfirmDelete(idx),
+                };
+            }
+            _ => return Some(key),
+        }
+        None
+    }
 }
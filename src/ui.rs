@@ -0,0 +1,394 @@
+// ui.rs
+
+use crate::backup;
+
+use std::io::{self, Stdout};
+use std::ops::{Deref, DerefMut};
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+use std::time::Duration;
+
+use crossterm::{
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers,
+        MouseButton, MouseEvent, MouseEventKind,
+    },
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use tui::{
+    backend::{Backend, CrosstermBackend},
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    Frame, Terminal,
+};
+
+/// How long to wait for input before redrawing
+const TICK: Duration = Duration::from_millis(200);
+
+/// Set while a screen owns the terminal, so cleanup runs exactly once
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static PANIC_HOOK: Once = Once::new();
+
+/// Put the terminal back the way the shell expects it
+fn restore_terminal() {
+    if ACTIVE.swap(false, Ordering::SeqCst) {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen, DisableMouseCapture, crossterm::cursor::Show);
+    }
+}
+
+/// Raw mode and the alternate screen for as long as it lives; dropped on any return, error or panic
+pub struct TerminalGuard {
+    terminal: Terminal<CrosstermBackend<Stdout>>,
+}
+
+impl TerminalGuard {
+    pub fn enter() -> io::Result<Self> {
+        // A panic message printed inside the alternate screen would vanish with it
+        PANIC_HOOK.call_once(|| {
+            let previous = panic::take_hook();
+            panic::set_hook(Box::new(move |info| {
+                restore_terminal();
+                previous(info);
+            }));
+        });
+
+        enable_raw_mode()?;
+        ACTIVE.store(true, Ordering::SeqCst);
+        if let Err(e) = execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture) {
+            restore_terminal();
+            return Err(e);
+        }
+        match Terminal::new(CrosstermBackend::new(io::stdout())) {
+            Ok(terminal) => Ok(TerminalGuard { terminal }),
+            Err(e) => {
+                restore_terminal();
+                Err(e)
+            }
+        }
+    }
+}
+
+impl Deref for TerminalGuard {
+    type Target = Terminal<CrosstermBackend<Stdout>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.terminal
+    }
+}
+
+impl DerefMut for TerminalGuard {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.terminal
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+/// The next key press or mouse event, or `None` if there was none within one redraw tick
+pub fn next_event() -> io::Result<Option<Event>> {
+    if !event::poll(TICK)? {
+        return Ok(None);
+    }
+    match event::read()? {
+        Event::Key(key) if key.kind == KeyEventKind::Release => Ok(None),
+        event @ (Event::Key(_) | Event::Mouse(_)) => Ok(Some(event)),
+        _ => Ok(None),
+    }
+}
+
+/// `[x]` or `[ ]`
+pub fn checkbox(checked: bool) -> &'static str {
+    if checked { "[x]" } else { "[ ]" }
+}
+
+/// Bordered bar at the bottom of a screen: the key help as its title, a status line inside
+pub fn help_bar<B: Backend>(f: &mut Frame<B>, area: Rect, help: &str, status: &str) {
+    let bar = Paragraph::new(status).block(Block::default().title(help).borders(Borders::ALL));
+    f.render_widget(bar, area);
+}
+
+/// Area in the middle of `area`, for popups
+pub fn centered(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    let width = area.width * percent_x / 100;
+    let height = area.height * percent_y / 100;
+    Rect::new(area.x + (area.width - width) / 2, area.y + (area.height - height) / 2, width, height)
+}
+
+/// `3 of 12 selected · 4.2 MiB`
+pub fn picked_status(picked: usize, total: usize, size: u64) -> String {
+    format!("✔ {} of {} selected · {}", picked, total, backup::format_size(size))
+}
+
+/// `a`, `n` and `i` on a checkbox list
+#[derive(Clone, Copy)]
+pub enum Bulk {
+    All,
+    Clear,
+    Invert,
+}
+
+impl Bulk {
+    pub fn from_key(code: KeyCode) -> Option<Self> {
+        match code {
+            KeyCode::Char('a') => Some(Bulk::All),
+            KeyCode::Char('n') => Some(Bulk::Clear),
+            KeyCode::Char('i') => Some(Bulk::Invert),
+            _ => None,
+        }
+    }
+
+    pub fn apply(self, checked: &mut bool) {
+        *checked = match self {
+            Bulk::All => true,
+            Bulk::Clear => false,
+            Bulk::Invert => !*checked,
+        };
+    }
+}
+
+/// Positions of the characters of `query` found in order in `text`, if all of them are.
+///
+/// Case is ignored unless the query has an uppercase letter.
+fn fuzzy_match(query: &str, text: &str) -> Option<Vec<usize>> {
+    let ignore_case = !query.chars().any(char::is_uppercase);
+    let fold = |c: char| if ignore_case { c.to_lowercase().next().unwrap_or(c) } else { c };
+    let mut positions = vec![];
+    let mut chars = text.chars().enumerate();
+    for q in query.chars().map(fold) {
+        let (pos, _) = chars.by_ref().find(|&(_, c)| fold(c) == q)?;
+        positions.push(pos);
+    }
+    Some(positions)
+}
+
+/// One row of a `SelectList`: a mark such as a checkbox, then the text that search looks at
+pub struct Line {
+    mark: String,
+    text: String,
+}
+
+impl Line {
+    pub fn new(mark: impl Into<String>, text: impl Into<String>) -> Self {
+        Line { mark: mark.into(), text: text.into() }
+    }
+}
+
+/// A bordered list with one highlighted row.
+///
+/// Handles moving (arrows, vim keys, paging, the mouse) and `/` search by itself;
+/// the screen only sees the keys it leaves alone.
+#[derive(Default)]
+pub struct SelectList {
+    lines: Vec<Line>,
+    /// Indices into `lines` that pass the search, with the positions of the matched characters
+    visible: Vec<(usize, Vec<usize>)>,
+    /// Position of the highlight within `visible`
+    cursor: Option<usize>,
+    /// First visible row on screen
+    offset: usize,
+    /// Where the list was last drawn, for paging and mouse clicks
+    area: Rect,
+    query: String,
+    typing: bool,
+}
+
+impl SelectList {
+    /// Replace the rows; call before drawing and handling input, whenever they may have changed
+    pub fn set_lines(&mut self, lines: Vec<Line>) {
+        self.lines = lines;
+        self.refilter();
+    }
+
+    fn refilter(&mut self) {
+        let keep = self.selected();
+        let old_cursor = self.cursor;
+        self.visible = self
+            .lines
+            .iter()
+            .enumerate()
+            .filter_map(|(i, line)| Some((i, fuzzy_match(&self.query, &line.text)?)))
+            .collect();
+        // The highlighted row stays highlighted while it still matches
+        self.cursor = match keep.and_then(|i| self.visible.iter().position(|(v, _)| *v == i)) {
+            Some(pos) => Some(pos),
+            None if self.visible.is_empty() => None,
+            None => Some(old_cursor.unwrap_or(0).min(self.visible.len() - 1)),
+        };
+    }
+
+    /// Index of the highlighted row in the lines given to `set_lines`
+    pub fn selected(&self) -> Option<usize> {
+        self.cursor.and_then(|c| self.visible.get(c)).map(|(i, _)| *i)
+    }
+
+    pub fn select(&mut self, index: Option<usize>) {
+        self.cursor = index.and_then(|i| self.visible.iter().position(|(v, _)| *v == i));
+    }
+
+    /// Rows that pass the search, in order
+    pub fn visible(&self) -> impl Iterator<Item = usize> + '_ {
+        self.visible.iter().map(|(i, _)| *i)
+    }
+
+    /// Whether a search is narrowing the rows down
+    pub fn is_filtered(&self) -> bool {
+        !self.query.is_empty()
+    }
+
+    fn page(&self) -> isize {
+        (self.area.height.saturating_sub(2) as isize).max(1)
+    }
+
+    fn step(&mut self, delta: isize, wrap: bool) {
+        let Some(cursor) = self.cursor else {
+            return;
+        };
+        let last = self.visible.len() as isize - 1;
+        let next = cursor as isize + delta;
+        let next = match next {
+            _ if !wrap => next.clamp(0, last),
+            n if n > last => 0,
+            n if n < 0 => last,
+            n => n,
+        };
+        self.cursor = Some(next as usize);
+    }
+
+    /// Deal with moving and searching; any other input comes back as a key for the screen.
+    ///
+    /// Clicking the highlighted row again comes back as `Space`.
+    pub fn handle(&mut self, event: Event) -> Option<KeyEvent> {
+        match event {
+            Event::Key(key) if self.typing => {
+                self.search_key(key);
+                None
+            }
+            Event::Key(key) => self.key(key),
+            Event::Mouse(mouse) => self.mouse(mouse),
+            _ => None,
+        }
+    }
+
+    fn search_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => {
+                self.query.clear();
+                self.typing = false;
+            }
+            KeyCode::Enter => self.typing = false,
+            KeyCode::Backspace => {
+                if self.query.pop().is_none() {
+                    self.typing = false;
+                }
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => self.query.push(c),
+            KeyCode::Up | KeyCode::Down | KeyCode::PageUp | KeyCode::PageDown => {
+                self.key(key);
+                return;
+            }
+            _ => return,
+        }
+        self.refilter();
+    }
+
+    fn key(&mut self, key: KeyEvent) -> Option<KeyEvent> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.step(-1, true),
+            KeyCode::Down | KeyCode::Char('j') => self.step(1, true),
+            KeyCode::PageUp => self.step(-self.page(), false),
+            KeyCode::PageDown => self.step(self.page(), false),
+            KeyCode::Char('u') if ctrl => self.step(-self.page(), false),
+            KeyCode::Char('d') if ctrl => self.step(self.page(), false),
+            KeyCode::Home | KeyCode::Char('g') => self.step(isize::MIN / 2, false),
+            KeyCode::End | KeyCode::Char('G') => self.step(isize::MAX / 2, false),
+            KeyCode::Char('/') => self.typing = true,
+            KeyCode::Esc if self.is_filtered() => {
+                self.query.clear();
+                self.refilter();
+            }
+            _ => return Some(key),
+        }
+        None
+    }
+
+    fn mouse(&mut self, mouse: MouseEvent) -> Option<KeyEvent> {
+        match mouse.kind {
+            MouseEventKind::ScrollDown => self.step(1, false),
+            MouseEventKind::ScrollUp => self.step(-1, false),
+            MouseEventKind::Down(MouseButton::Left) => {
+                let inner = self.area.y + 1..self.area.y + self.area.height.saturating_sub(1);
+                let across = self.area.x..self.area.x + self.area.width;
+                if !inner.contains(&mouse.row) || !across.contains(&mouse.column) {
+                    return None;
+                }
+                let pos = self.offset + (mouse.row - inner.start) as usize;
+                if pos >= self.visible.len() {
+                    return None;
+                }
+                if self.cursor == Some(pos) {
+                    return Some(KeyEvent::new(KeyCode::Char(' '), KeyModifiers::NONE));
+                }
+                self.cursor = Some(pos);
+            }
+            _ => {}
+        }
+        None
+    }
+
+    pub fn render<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect, title: &str) {
+        self.area = area;
+        let height = area.height.saturating_sub(2) as usize;
+
+        // Scroll just enough to keep the highlight on screen
+        if let Some(cursor) = self.cursor {
+            if cursor < self.offset {
+                self.offset = cursor;
+            } else if cursor >= self.offset + height {
+                self.offset = cursor + 1 - height.max(1);
+            }
+        }
+        self.offset = self.offset.min(self.visible.len().saturating_sub(height));
+
+        let matched = Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+        let rows: Vec<ListItem> = self
+            .visible
+            .iter()
+            .skip(self.offset)
+            .take(height)
+            .map(|(i, positions)| {
+                let line = &self.lines[*i];
+                let mut spans = vec![Span::raw(line.mark.as_str())];
+                spans.extend(line.text.chars().enumerate().map(|(pos, c)| {
+                    let style = if positions.contains(&pos) { matched } else { Style::default() };
+                    Span::styled(c.to_string(), style)
+                }));
+                ListItem::new(Spans::from(spans))
+            })
+            .collect();
+
+        let title = if self.typing || self.is_filtered() {
+            let caret = if self.typing { "▏" } else { "" };
+            format!("{}  🔍 {}{} ({}/{})", title, self.query, caret, self.visible.len(), self.lines.len())
+        } else {
+            title.to_string()
+        };
+        let list = List::new(rows)
+            .block(Block::default().title(title).borders(Borders::ALL))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+            .highlight_symbol(">>");
+
+        let mut state = ListState::default();
+        state.select(self.cursor.map(|c| c - self.offset));
+        f.render_stateful_widget(list, area, &mut state);
+    }
+}
@@ -0,0 +1,330 @@
+// malicious_archives.rs
+//
+// Restores hand-crafted hostile archives through the real binary and checks nothing escapes.
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use tar::{EntryType, Header};
+
+/// One tar entry, written with raw header bytes so unsafe names survive
+enum Fixture<'a> {
+    File(&'a str, &'a [u8]),
+    Dir(&'a str),
+    Symlink(&'a str, &'a str),
+    HardLink(&'a str, &'a str),
+}
+
+struct Sandbox {
+    _tmp: tempfile::TempDir,
+    root: PathBuf,
+    home: PathBuf,
+    work: PathBuf,
+}
+
+impl Sandbox {
+    fn new() -> Self {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("sandbox");
+        let home = root.join("home");
+        let work = root.join("work");
+        fs::create_dir_all(&home).unwrap();
+        fs::create_dir_all(&work).unwrap();
+        Sandbox { _tmp: tmp, root, home, work }
+    }
+
+    fn archive(&self, entries: &[Fixture]) {
+        let file = fs::File::create(self.work.join("a.tar.gz")).unwrap();
+        let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+        for entry in entries {
+            let (name, kind, data, link): (&str, EntryType, &[u8], Option<&str>) = match entry {
+                Fixture::File(name, data) => (name, EntryType::Regular, data, None),
+                Fixture::Dir(name) => (name, EntryType::Directory, b"", None),
+                Fixture::Symlink(name, target) => (name, EntryType::Symlink, b"", Some(target)),
+                Fixture::HardLink(name, target) => (name, EntryType::Link, b"", Some(target)),
+            };
+
+            let mut header = Header::new_gnu();
+            let gnu = header.as_gnu_mut().unwrap();
+            gnu.name[..name.len()].copy_from_slice(name.as_bytes());
+            if let Some(link) = link {
+                gnu.linkname[..link.len()].copy_from_slice(link.as_bytes());
+            }
+            header.set_entry_type(kind);
+            header.set_mode(if kind == EntryType::Directory { 0o755 } else { 0o644 });
+            header.set_size(data.len() as u64);
+            header.set_mtime(1_700_000_000);
+            header.set_cksum();
+            builder.append(&header, data).unwrap();
+        }
+
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    fn manifest(&self, items: &[(&str, &str)]) {
+        let toml: String = items
+            .iter()
+            .map(|(name, path)| format!("[[items]]\nname = \"{name}\"\npath = \"{path}\"\nselected = true\n\n"))
+            .collect();
+        fs::write(self.work.join("a.manifest.toml"), toml).unwrap();
+    }
+
+    fn config(&self, contents: &str) {
+        fs::write(self.work.join("config_targets.toml"), contents).unwrap();
+    }
+
+    /// Restore `a.tar.gz`; these fixtures are unsigned, so that check is waived
+    fn restore(&self, extra: &[&str]) -> Output {
+        self.run(&[&["--restore", "a.tar.gz", "a.manifest.toml", "--allow-unsigned"], extra].concat())
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        let mut child = self.spawn(args);
+        // A refused restore exits before reading the confirmation, so a broken pipe is fine
+        let _ = child.stdin.take().unwrap().write_all(b"y\nn\n");
+        child.wait_with_output().unwrap()
+    }
+
+    fn spawn(&self, args: &[&str]) -> Child {
+        Command::new(env!("CARGO_BIN_EXE_restitch"))
+            .args(args)
+            .current_dir(&self.work)
+            .env("HOME", &self.home)
+            .env_remove("XDG_CONFIG_HOME")
+            .env_remove("XDG_DATA_HOME")
+            .env_remove("XDG_STATE_HOME")
+            .env_remove("XDG_CACHE_HOME")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap()
+    }
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+fn assert_refused(output: &Output, reason: &str) {
+    let out = stdout(output);
+    assert!(!output.status.success(), "restore should fail:\n{out}");
+    assert!(out.contains("Refusing to restore"), "missing refusal:\n{out}");
+    assert!(out.contains(reason), "expected '{reason}' in:\n{out}");
+}
+
+fn assert_untouched(home: &Path) {
+    assert!(!home.join(".config").exists(), "restore wrote into home");
+}
+
+#[test]
+fn benign_archive_restores() {
+    let sb = Sandbox::new();
+    sb.archive(&[
+        Fixture::Dir(".config/app"),
+        Fixture::File(".config/app/a.conf", b"ok\n"),
+        Fixture::Symlink(".config/app/current", "a.conf"),
+    ]);
+    sb.manifest(&[("app", "~/.config/app")]);
+
+    let output = sb.restore(&[]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert_eq!(fs::read_to_string(sb.home.join(".config/app/a.conf")).unwrap(), "ok\n");
+    assert_eq!(fs::read_link(sb.home.join(".config/app/current")).unwrap(), Path::new("a.conf"));
+}
+
+#[test]
+fn unsigned_archive_is_refused() {
+    let sb = Sandbox::new();
+    sb.archive(&[Fixture::File(".config/app/a.conf", b"ok\n")]);
+    sb.manifest(&[("app", "~/.config/app")]);
+
+    assert_refused(&sb.run(&["--restore", "a.tar.gz", "a.manifest.toml"]), "archive is not signed");
+    assert_untouched(&sb.home);
+}
+
+#[test]
+fn parent_dir_entry_is_refused() {
+    let sb = Sandbox::new();
+    sb.archive(&[
+        Fixture::File(".config/app/a.conf", b"ok\n"),
+        Fixture::File(".config/app/../../../escaped", b"pwned\n"),
+    ]);
+    sb.manifest(&[("app", "~/.config/app")]);
+
+    assert_refused(&sb.restore(&[]), "points outside the archive");
+    assert!(!sb.root.join("escaped").exists());
+    assert_untouched(&sb.home);
+}
+
+#[test]
+fn absolute_entry_is_refused() {
+    let sb = Sandbox::new();
+    let target = sb.root.join("absolute");
+    sb.archive(&[
+        Fixture::File(".config/app/a.conf", b"ok\n"),
+        Fixture::File(target.to_str().unwrap(), b"pwned\n"),
+    ]);
+    sb.manifest(&[("app", "~/.config/app")]);
+
+    assert_refused(&sb.restore(&[]), "points outside the archive");
+    assert!(!target.exists());
+    assert_untouched(&sb.home);
+}
+
+#[test]
+fn absolute_symlink_is_refused() {
+    let sb = Sandbox::new();
+    let outside = sb.root.join("outside");
+    fs::create_dir_all(&outside).unwrap();
+    sb.archive(&[
+        Fixture::Symlink(".config/app/link", outside.to_str().unwrap()),
+        Fixture::File(".config/app/link/pwned", b"pwned\n"),
+    ]);
+    sb.manifest(&[("app", "~/.config/app")]);
+
+    assert_refused(&sb.restore(&[]), "escapes its item");
+    assert!(!outside.join("pwned").exists());
+    assert_untouched(&sb.home);
+}
+
+#[test]
+fn relative_symlink_escape_is_refused() {
+    let sb = Sandbox::new();
+    sb.archive(&[
+        Fixture::Symlink(".config/app/link", "../../.."),
+        Fixture::File(".config/app/link/pwned", b"pwned\n"),
+    ]);
+    sb.manifest(&[("app", "~/.config/app")]);
+
+    assert_refused(&sb.restore(&[]), "escapes its item");
+    assert!(!sb.root.join("pwned").exists());
+    assert_untouched(&sb.home);
+}
+
+#[test]
+fn write_through_symlink_is_refused() {
+    let sb = Sandbox::new();
+    sb.archive(&[
+        Fixture::Dir(".config/app/real"),
+        Fixture::Symlink(".config/app/link", "real"),
+        Fixture::File(".config/app/link/a.conf", b"sneaky\n"),
+    ]);
+    sb.manifest(&[("app", "~/.config/app")]);
+
+    assert_refused(&sb.restore(&[]), "written through symlink");
+    assert_untouched(&sb.home);
+}
+
+#[test]
+fn hard_link_is_refused() {
+    let sb = Sandbox::new();
+    sb.archive(&[
+        Fixture::File(".config/app/a.conf", b"ok\n"),
+        Fixture::HardLink(".config/app/passwd", "/etc/passwd"),
+    ]);
+    sb.manifest(&[("app", "~/.config/app")]);
+
+    assert_refused(&sb.restore(&[]), "unsupported type");
+    assert_untouched(&sb.home);
+}
+
+#[test]
+fn manifest_parent_dir_is_rejected() {
+    let sb = Sandbox::new();
+    sb.archive(&[Fixture::File("escaped", b"pwned\n")]);
+    sb.manifest(&[("evil", "~/../../escaped")]);
+
+    let output = sb.restore(&[]);
+    assert!(stdout(&output).contains("REJECTED"), "{}", stdout(&output));
+    assert!(!sb.root.join("escaped").exists());
+}
+
+#[test]
+fn manifest_system_path_needs_allowed_root() {
+    let sb = Sandbox::new();
+    let target_root = sb.root.join("target");
+    fs::create_dir_all(&target_root).unwrap();
+    sb.archive(&[Fixture::File("__root__/etc/passwd", b"evil:x:0:0::/:/bin/sh\n")]);
+    sb.manifest(&[("passwd", "/etc/passwd")]);
+
+    let root_arg = target_root.to_str().unwrap();
+    let output = sb.restore(&["--target-root", root_arg, "--no-escalate"]);
+    assert!(stdout(&output).contains("REJECTED"), "{}", stdout(&output));
+    assert!(!target_root.join("etc/passwd").exists());
+
+    // Explicitly allowing the root lets the same item through, still inside the target root
+    let output = sb.restore(&["--target-root", root_arg, "--no-escalate", "--allow-root", "/etc"]);
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(target_root.join("etc/passwd").exists());
+}
+
+#[test]
+fn entry_count_limit_is_enforced() {
+    let sb = Sandbox::new();
+    sb.config("config = []\n\n[restore]\nmax_entries = 3\n");
+    sb.archive(&[
+        Fixture::File(".config/app/1", b"1"),
+        Fixture::File(".config/app/2", b"2"),
+        Fixture::File(".config/app/3", b"3"),
+        Fixture::File(".config/app/4", b"4"),
+    ]);
+    sb.manifest(&[("app", "~/.config/app")]);
+
+    assert_refused(&sb.restore(&[]), "more than 3 entries");
+    assert_untouched(&sb.home);
+}
+
+#[test]
+fn total_size_limit_is_enforced() {
+    let sb = Sandbox::new();
+    sb.config("config = []\n\n[restore]\nmax_total_size = \"1K\"\n");
+    let big = vec![b'x'; 4096];
+    sb.archive(&[Fixture::File(".config/app/big", &big)]);
+    sb.manifest(&[("app", "~/.config/app")]);
+
+    assert_refused(&sb.restore(&[]), "unpacks to more than");
+    assert_untouched(&sb.home);
+}
+
+#[test]
+fn archive_swapped_after_verification_is_not_restored() {
+    let sb = Sandbox::new();
+    sb.archive(&[Fixture::File(".config/app/a.conf", b"ok\n")]);
+    sb.manifest(&[("app", "~/.config/app")]);
+
+    let mut child = sb.spawn(&["--restore", "a.tar.gz", "a.manifest.toml", "--allow-unsigned"]);
+    let mut stdout = child.stdout.take().unwrap();
+    let mut seen = vec![];
+    let mut chunk = [0u8; 4096];
+    while !String::from_utf8_lossy(&seen).contains("Proceed with restore?") {
+        let read = stdout.read(&mut chunk).unwrap();
+        assert!(read > 0, "no confirmation prompt:\n{}", String::from_utf8_lossy(&seen));
+        seen.extend_from_slice(&chunk[..read]);
+    }
+
+    // The plan was checked; now the archive on disk changes under it
+    sb.archive(&[Fixture::File(".config/app/a.conf", b"pwned\n")]);
+    child.stdin.take().unwrap().write_all(b"y\n").unwrap();
+    stdout.read_to_end(&mut seen).unwrap();
+    let status = child.wait().unwrap();
+
+    assert!(status.success(), "{}", String::from_utf8_lossy(&seen));
+    assert_eq!(fs::read_to_string(sb.home.join(".config/app/a.conf")).unwrap(), "ok\n");
+}
+
+#[test]
+fn single_file_item_symlink_escape_is_refused() {
+    for target in ["../x", "sibling", ".config/../x"] {
+        let sb = Sandbox::new();
+        sb.archive(&[Fixture::Symlink(".bashrc", target)]);
+        sb.manifest(&[("bash", "~/.bashrc")]);
+
+        assert_refused(&sb.restore(&[]), "escapes its item");
+        assert!(!sb.home.join(".bashrc").exists(), "'{target}' was restored");
+    }
+}